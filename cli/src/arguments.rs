@@ -15,7 +15,7 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 const ARGUMENT_AUTHOR: &'static str = "Tobias Raatiniemi <raatiniemi@gmail.com>";
 const ARGUMENT_VERSION: &'static str = "0.0.1";
@@ -23,12 +23,42 @@ const ARGUMENT_ABOUT: &'static str = "Create symbolic links from target director
 
 const ARGUMENT_CONFIGURATION_HELP: &'static str = "Path to the configuration file.";
 const ARGUMENT_DRY_RUN_HELP: &'static str = "Run application without performing any changes.";
+const ARGUMENT_JOBS_HELP: &str = "Number of threads used to scan the sources and targets, defaults to one per CPU.";
+
+const COMMAND_CHECK_ABOUT: &str = "Validate the configuration file and report every problem found.";
+const COMMAND_UNDO_ABOUT: &str = "Revert the links and directories created by a run, and restore anything it replaced.";
+const COMMAND_PRUNE_ABOUT: &str = "Remove broken links, and links that no link map would produce, from the targets.";
+const ARGUMENT_REMOVE_EMPTY_DIRECTORIES_HELP: &str = "Remove directories within the targets that are left empty.";
+const COMMAND_RECONCILE_ABOUT: &str = "Create, move and delete links so that the targets match the link maps.";
+const ARGUMENT_RUN_ID_HELP: &str = "Id of the run to undo, defaults to the latest run that hasn't been undone.";
 
 #[derive(Parser, Debug)]
 #[command(author = ARGUMENT_AUTHOR, version = ARGUMENT_VERSION, about = ARGUMENT_ABOUT, long_about = None)]
 pub(crate) struct Arguments {
     #[arg(short, long, help = ARGUMENT_CONFIGURATION_HELP)]
     pub(crate) configuration: String,
-    #[arg(long, help = ARGUMENT_DRY_RUN_HELP)]
+    #[arg(long, global = true, help = ARGUMENT_DRY_RUN_HELP)]
     pub(crate) dry_run: bool,
+    #[arg(short, long, global = true, default_value_t = 0, help = ARGUMENT_JOBS_HELP)]
+    pub(crate) jobs: usize,
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    #[command(about = COMMAND_CHECK_ABOUT)]
+    Check,
+    #[command(about = COMMAND_UNDO_ABOUT)]
+    Undo {
+        #[arg(help = ARGUMENT_RUN_ID_HELP)]
+        run_id: Option<String>,
+    },
+    #[command(about = COMMAND_PRUNE_ABOUT)]
+    Prune {
+        #[arg(long, help = ARGUMENT_REMOVE_EMPTY_DIRECTORIES_HELP)]
+        remove_empty_directories: bool,
+    },
+    #[command(about = COMMAND_RECONCILE_ABOUT)]
+    Reconcile,
 }
@@ -0,0 +1,1005 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use globset::GlobBuilder;
+use json::JsonValue;
+use regex::bytes::Regex;
+
+use crate::configuration::{
+    ConflictPolicy, ExcludeScope, ExcludeType, LinkMode, MatchStrategy, canonicalize_configuration_path,
+    configuration_directory, parse_mode, resolve_group, resolve_include, resolve_user,
+    validate_link_name,
+};
+use crate::configuration_format::{ConfigurationFormat, parse_document};
+use crate::expand_path::expand_path;
+
+const CONFIGURATION_KEYS: [&str; 11] = [
+    "source", "sources", "targets", "excludes", "linkMaps", "matchStrategy", "relative", "transactional", "journal",
+    "include", "followSourceLinks",
+];
+const INCLUDED_CONFIGURATION_KEYS: [&str; 4] = ["targets", "excludes", "linkMaps", "include"];
+const SOURCE_KEYS: [&str; 3] = ["path", "priority", "maxDepth"];
+const TARGET_KEYS: [&str; 6] = ["path", "directoryMode", "owner", "group", "onConflict", "maxDepth"];
+const EXCLUDE_KEYS: [&str; 4] = ["glob", "regex", "type", "scope"];
+const LINK_MAP_KEYS: [&str; 5] = ["regex", "target", "name", "relative", "mode"];
+
+/// Describes a single problem with the configuration, the path uses the same notation as the
+/// configuration file, e.g. `linkMaps[3].regex`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Diagnostic {
+    pub path: String,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn new(path: &str, message: &str) -> Self {
+        Diagnostic {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+pub fn check_configuration_file(path: &str) -> Vec<Diagnostic> {
+    match fs::read_to_string(path) {
+        Ok(data) => {
+            let format = ConfigurationFormat::from_path(path);
+            let mut parents = vec![canonicalize_configuration_path(path)];
+            check_configuration_with_parents(&data, format, &configuration_directory(path), &mut parents)
+        }
+        Err(e) => vec![
+            Diagnostic::new("", &format!("unable to read configuration file at path {}: {}", path, e))
+        ],
+    }
+}
+
+/// Relative paths within the configuration are resolved against the directory, same as when the
+/// configuration is read.
+#[cfg(test)]
+pub fn check_configuration(configuration: &str, format: ConfigurationFormat, directory: &Path) -> Vec<Diagnostic> {
+    check_configuration_with_parents(configuration, format, directory, &mut Vec::new())
+}
+
+/// The targets can also come from the included files, so they are only missing when neither the
+/// configuration nor any of its included files define them.
+fn check_configuration_with_parents(
+    configuration: &str,
+    format: ConfigurationFormat,
+    directory: &Path,
+    parents: &mut Vec<PathBuf>,
+) -> Vec<Diagnostic> {
+    let data = match parse_document(format, configuration) {
+        Ok(data) => data,
+        Err(e) => return vec![Diagnostic::new("", &e.to_string())],
+    };
+    if !data.is_object() {
+        return vec![Diagnostic::new("", "configuration must be an object")];
+    }
+
+    let mut include_diagnostics = Vec::new();
+    let included_targets = check_includes(&mut include_diagnostics, "include", &data["include"], directory, parents);
+    let mut diagnostics = Vec::new();
+    check_unknown_keys(&mut diagnostics, "", &data, &CONFIGURATION_KEYS);
+    check_sources(&mut diagnostics, &data["source"], &data["sources"], directory);
+    if !included_targets || !data["targets"].is_null() {
+        check_targets(&mut diagnostics, &data["targets"], directory);
+    }
+    check_excludes(&mut diagnostics, &data["excludes"]);
+    check_link_maps(&mut diagnostics, &data["linkMaps"], directory);
+    check_match_strategy(&mut diagnostics, &data["matchStrategy"]);
+    check_optional_boolean(&mut diagnostics, "relative", &data["relative"]);
+    check_optional_boolean(&mut diagnostics, "transactional", &data["transactional"]);
+    check_optional_boolean(&mut diagnostics, "followSourceLinks", &data["followSourceLinks"]);
+    check_journal(&mut diagnostics, &data["journal"], directory);
+    diagnostics.extend(include_diagnostics);
+    diagnostics
+}
+
+/// Included configuration files can only contain `targets`, `excludes`, `linkMaps` and `include`,
+/// and the diagnostics are prefixed with the path of the included file. Returns whether the file,
+/// or one of the files it includes, defines targets.
+fn check_included_configuration_file(
+    diagnostics: &mut Vec<Diagnostic>,
+    path: &str,
+    parents: &mut Vec<PathBuf>,
+) -> bool {
+    let canonical_path = canonicalize_configuration_path(path);
+    if parents.contains(&canonical_path) {
+        diagnostics.push(Diagnostic::new(path, "configuration file includes itself"));
+        return false;
+    }
+
+    let data = match fs::read_to_string(path) {
+        Ok(data) if data.is_empty() => return false,
+        Ok(data) => data,
+        Err(e) => {
+            diagnostics.push(Diagnostic::new(path, &format!("unable to read configuration file: {}", e)));
+            return false;
+        }
+    };
+    let data = match parse_document(ConfigurationFormat::from_path(path), &data) {
+        Ok(data) => data,
+        Err(e) => {
+            diagnostics.push(Diagnostic::new(path, &e.to_string()));
+            return false;
+        }
+    };
+    if !data.is_object() {
+        diagnostics.push(Diagnostic::new(path, "configuration must be an object"));
+        return false;
+    }
+
+    let directory = configuration_directory(path);
+    let mut file_diagnostics = Vec::new();
+    check_unknown_keys(&mut file_diagnostics, "", &data, &INCLUDED_CONFIGURATION_KEYS);
+    if !data["targets"].is_null() {
+        check_targets(&mut file_diagnostics, &data["targets"], &directory);
+    }
+    check_excludes(&mut file_diagnostics, &data["excludes"]);
+    check_link_maps(&mut file_diagnostics, &data["linkMaps"], &directory);
+    diagnostics.extend(file_diagnostics.into_iter()
+        .map(|v| Diagnostic {
+            path: if v.path.is_empty() { path.to_string() } else { format!("{}:{}", path, v.path) },
+            message: v.message,
+        }));
+    parents.push(canonical_path);
+    let include_path = format!("{}:include", path);
+    let included_targets = check_includes(diagnostics, &include_path, &data["include"], &directory, parents);
+    parents.pop();
+    !data["targets"].is_null() || included_targets
+}
+
+fn join_path(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn check_unknown_keys(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue, keys: &[&str]) {
+    value.entries()
+        .filter(|(key, _)| !keys.contains(key))
+        .for_each(|(key, _)| {
+            let message = format!("unknown key {:?}", key);
+            diagnostics.push(Diagnostic::new(path, &message));
+        });
+}
+
+fn check_required_string(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue) -> Option<String> {
+    if value.is_null() {
+        diagnostics.push(Diagnostic::new(path, "is missing"));
+        return None;
+    }
+
+    match value.as_str() {
+        Some("") => {
+            diagnostics.push(Diagnostic::new(path, "must not be empty"));
+            None
+        }
+        Some(v) => Some(v.to_string()),
+        None => {
+            diagnostics.push(Diagnostic::new(path, "must be a string"));
+            None
+        }
+    }
+}
+
+fn check_required_path(
+    diagnostics: &mut Vec<Diagnostic>,
+    path: &str,
+    value: &JsonValue,
+    directory: &Path,
+) -> Option<String> {
+    check_required_string(diagnostics, path, value)
+        .and_then(|v| match expand_path(&v, directory) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                diagnostics.push(Diagnostic::new(path, &e.to_string()));
+                None
+            }
+        })
+}
+
+fn check_directory(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue, directory: &Path) {
+    if let Some(v) = check_required_path(diagnostics, path, value, directory) {
+        if !Path::new(&v).is_dir() {
+            diagnostics.push(Diagnostic::new(path, "directory does not exist"));
+        }
+    }
+}
+
+/// The patterns are matched against the raw bytes of the basename, same as when linking, i.e. a
+/// pattern that can match invalid UTF-8, e.g. `(?-u:\xff)`, is valid.
+fn check_regex(diagnostics: &mut Vec<Diagnostic>, path: &str, pattern: &str) {
+    if let Err(e) = Regex::from_str(pattern) {
+        diagnostics.push(Diagnostic::new(path, &format!("invalid regex: {}", e)));
+    }
+}
+
+fn check_glob(diagnostics: &mut Vec<Diagnostic>, path: &str, pattern: &str) {
+    if let Err(e) = GlobBuilder::new(pattern).build() {
+        diagnostics.push(Diagnostic::new(path, &format!("invalid glob: {}", e)));
+    }
+}
+
+fn check_optional_boolean(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue) {
+    if !value.is_null() && !value.is_boolean() {
+        diagnostics.push(Diagnostic::new(path, "must be a boolean"));
+    }
+}
+
+fn check_array<'a>(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &'a JsonValue) -> Option<&'a [JsonValue]> {
+    match value {
+        JsonValue::Array(ref values) => Some(values),
+        _ => {
+            diagnostics.push(Diagnostic::new(path, "must be an array"));
+            None
+        }
+    }
+}
+
+fn check_sources(diagnostics: &mut Vec<Diagnostic>, source: &JsonValue, sources: &JsonValue, directory: &Path) {
+    if source.is_null() && sources.is_null() {
+        diagnostics.push(Diagnostic::new("source", "is missing, either source or sources is required"));
+        return;
+    }
+
+    if !source.is_null() {
+        check_directory(diagnostics, "source", source, directory);
+    }
+    if sources.is_null() {
+        return;
+    }
+    if let Some(sources) = check_array(diagnostics, "sources", sources) {
+        sources.iter()
+            .enumerate()
+            .for_each(|(index, source)| {
+                check_source(diagnostics, &format!("sources[{}]", index), source, directory)
+            });
+    }
+}
+
+fn check_source(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue, directory: &Path) {
+    if value.is_string() {
+        check_directory(diagnostics, path, value, directory);
+        return;
+    }
+    if !value.is_object() {
+        diagnostics.push(Diagnostic::new(path, "must be a string or an object"));
+        return;
+    }
+
+    check_unknown_keys(diagnostics, path, value, &SOURCE_KEYS);
+    check_directory(diagnostics, &join_path(path, "path"), &value["path"], directory);
+    if value.has_key("priority") && value["priority"].as_i64().is_none() {
+        diagnostics.push(Diagnostic::new(&join_path(path, "priority"), "must be an integer"));
+    }
+    check_max_depth(diagnostics, path, value);
+}
+
+fn check_targets(diagnostics: &mut Vec<Diagnostic>, value: &JsonValue, directory: &Path) {
+    if value.is_null() {
+        diagnostics.push(Diagnostic::new("targets", "is missing"));
+        return;
+    }
+
+    if let Some(targets) = check_array(diagnostics, "targets", value) {
+        if targets.is_empty() {
+            diagnostics.push(Diagnostic::new("targets", "must not be empty"));
+        }
+        targets.iter()
+            .enumerate()
+            .for_each(|(index, target)| {
+                check_target(diagnostics, &format!("targets[{}]", index), target, directory)
+            });
+    }
+}
+
+fn check_target(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue, directory: &Path) {
+    if value.is_string() {
+        check_directory(diagnostics, path, value, directory);
+        return;
+    }
+    if !value.is_object() {
+        diagnostics.push(Diagnostic::new(path, "must be a string or an object"));
+        return;
+    }
+
+    check_unknown_keys(diagnostics, path, value, &TARGET_KEYS);
+    check_directory(diagnostics, &join_path(path, "path"), &value["path"], directory);
+    [
+        ("directoryMode", parse_mode as fn(&str) -> Result<u32, String>),
+        ("owner", resolve_user),
+        ("group", resolve_group),
+    ].iter()
+        .filter(|(key, _)| value.has_key(key))
+        .for_each(|(key, parse)| {
+            let path = join_path(path, key);
+            if let Some(v) = check_required_string(diagnostics, &path, &value[*key]) {
+                if let Err(e) = parse(&v) {
+                    diagnostics.push(Diagnostic::new(&path, &e));
+                }
+            }
+        });
+
+    if value.has_key("onConflict") {
+        let path = join_path(path, "onConflict");
+        if let Some(v) = check_required_string(diagnostics, &path, &value["onConflict"]) {
+            if let Err(e) = ConflictPolicy::from_str(&v) {
+                diagnostics.push(Diagnostic::new(&path, &e));
+            }
+        }
+    }
+    check_max_depth(diagnostics, path, value);
+}
+
+fn check_max_depth(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue) {
+    if value.has_key("maxDepth") && value["maxDepth"].as_usize().is_none() {
+        diagnostics.push(Diagnostic::new(&join_path(path, "maxDepth"), "must be a non-negative integer"));
+    }
+}
+
+fn check_excludes(diagnostics: &mut Vec<Diagnostic>, value: &JsonValue) {
+    if value.is_null() {
+        return;
+    }
+
+    if let Some(excludes) = check_array(diagnostics, "excludes", value) {
+        excludes.iter()
+            .enumerate()
+            .for_each(|(index, exclude)| check_exclude(diagnostics, &format!("excludes[{}]", index), exclude));
+    }
+}
+
+fn check_exclude(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue) {
+    if value.is_string() {
+        if let Some(pattern) = check_required_string(diagnostics, path, value) {
+            check_glob(diagnostics, path, &pattern);
+        }
+        return;
+    }
+    if !value.is_object() {
+        diagnostics.push(Diagnostic::new(path, "must be a string or an object"));
+        return;
+    }
+
+    check_unknown_keys(diagnostics, path, value, &EXCLUDE_KEYS);
+    match (value.has_key("glob"), value.has_key("regex")) {
+        (true, true) => diagnostics.push(Diagnostic::new(path, "must not have both glob and regex")),
+        (false, false) => diagnostics.push(Diagnostic::new(path, "must have either glob or regex")),
+        (true, false) => {
+            let path = join_path(path, "glob");
+            if let Some(pattern) = check_required_string(diagnostics, &path, &value["glob"]) {
+                check_glob(diagnostics, &path, &pattern);
+            }
+        }
+        (false, true) => {
+            let path = join_path(path, "regex");
+            if let Some(pattern) = check_required_string(diagnostics, &path, &value["regex"]) {
+                check_regex(diagnostics, &path, &pattern);
+            }
+        }
+    }
+
+    if value.has_key("type") {
+        let path = join_path(path, "type");
+        if let Some(v) = check_required_string(diagnostics, &path, &value["type"]) {
+            if let Err(e) = ExcludeType::from_str(&v) {
+                diagnostics.push(Diagnostic::new(&path, &e.to_string()));
+            }
+        }
+    }
+    if value.has_key("scope") {
+        let path = join_path(path, "scope");
+        if let Some(v) = check_required_string(diagnostics, &path, &value["scope"]) {
+            if let Err(e) = ExcludeScope::from_str(&v) {
+                diagnostics.push(Diagnostic::new(&path, &e.to_string()));
+            }
+        }
+    }
+}
+
+fn check_match_strategy(diagnostics: &mut Vec<Diagnostic>, value: &JsonValue) {
+    if value.is_null() {
+        return;
+    }
+
+    if let Some(v) = check_required_string(diagnostics, "matchStrategy", value) {
+        if let Err(e) = MatchStrategy::from_str(&v) {
+            diagnostics.push(Diagnostic::new("matchStrategy", &e.to_string()));
+        }
+    }
+}
+
+/// The journal directory is created when needed, so it only has to be a valid path.
+fn check_journal(diagnostics: &mut Vec<Diagnostic>, value: &JsonValue, directory: &Path) {
+    if value.is_null() {
+        return;
+    }
+
+    if let Some(v) = check_required_string(diagnostics, "journal", value) {
+        if let Err(e) = expand_path(&v, directory) {
+            diagnostics.push(Diagnostic::new("journal", &e.to_string()));
+        }
+    }
+}
+
+/// Returns whether any of the included files defines targets.
+fn check_includes(
+    diagnostics: &mut Vec<Diagnostic>,
+    path: &str,
+    value: &JsonValue,
+    directory: &Path,
+    parents: &mut Vec<PathBuf>,
+) -> bool {
+    if value.is_null() {
+        return false;
+    }
+
+    let mut included_targets = false;
+    if let Some(includes) = check_array(diagnostics, path, value) {
+        includes.iter()
+            .enumerate()
+            .for_each(|(index, include)| {
+                let path = format!("{}[{}]", path, index);
+                if let Some(include) = check_required_string(diagnostics, &path, include) {
+                    match resolve_include(&include, directory) {
+                        Ok(files) => files.iter()
+                            .for_each(|v| {
+                                included_targets |= check_included_configuration_file(diagnostics, v, parents)
+                            }),
+                        Err(e) => diagnostics.push(Diagnostic::new(&path, &e.to_string())),
+                    }
+                }
+            });
+    }
+    included_targets
+}
+
+fn check_link_maps(diagnostics: &mut Vec<Diagnostic>, value: &JsonValue, directory: &Path) {
+    if value.is_null() {
+        return;
+    }
+
+    if let Some(link_maps) = check_array(diagnostics, "linkMaps", value) {
+        link_maps.iter()
+            .enumerate()
+            .for_each(|(index, link_map)| {
+                check_link_map(diagnostics, &format!("linkMaps[{}]", index), link_map, directory)
+            });
+    }
+}
+
+fn check_link_map(diagnostics: &mut Vec<Diagnostic>, path: &str, value: &JsonValue, directory: &Path) {
+    if !value.is_object() {
+        diagnostics.push(Diagnostic::new(path, "must be an object"));
+        return;
+    }
+
+    check_unknown_keys(diagnostics, path, value, &LINK_MAP_KEYS);
+    let regex_path = join_path(path, "regex");
+    let regex = check_required_string(diagnostics, &regex_path, &value["regex"])
+        .and_then(|pattern| {
+            check_regex(diagnostics, &regex_path, &pattern);
+            Regex::from_str(&pattern).ok()
+        });
+    check_required_path(diagnostics, &join_path(path, "target"), &value["target"], directory);
+
+    check_optional_boolean(diagnostics, &join_path(path, "relative"), &value["relative"]);
+    if value.has_key("mode") {
+        let path = join_path(path, "mode");
+        if let Some(v) = check_required_string(diagnostics, &path, &value["mode"]) {
+            if let Err(e) = LinkMode::parse_modes(&v) {
+                diagnostics.push(Diagnostic::new(&path, &e));
+            }
+        }
+    }
+    if value.has_key("name") {
+        let path = join_path(path, "name");
+        let name = check_required_string(diagnostics, &path, &value["name"]);
+        if let Some(Err(e)) = name.as_ref().map(|v| validate_link_name(v.as_bytes())) {
+            diagnostics.push(Diagnostic::new(&path, &e));
+        }
+        if let (Some(name), Some(regex)) = (name, regex) {
+            check_name_template(diagnostics, &path, &name, &regex);
+        }
+    }
+}
+
+/// Capture groups referenced by the template that do not exist in the regex would be expanded to
+/// an empty string, which is most likely a mistake.
+fn check_name_template(diagnostics: &mut Vec<Diagnostic>, path: &str, template: &str, regex: &Regex) {
+    let references = regex::Regex::new(r"\$(?:\$|\{([^}]*)\}|([0-9A-Za-z_]+))")
+        .expect("Unable to build regex for capture group references");
+
+    references.captures_iter(template)
+        .filter_map(|v| v.get(1).or_else(|| v.get(2)))
+        .map(|v| v.as_str())
+        .filter(|name| match name.parse::<usize>() {
+            Ok(index) => index >= regex.captures_len(),
+            Err(_) => !regex.capture_names().any(|v| v == Some(*name)),
+        })
+        .for_each(|name| {
+            let message = format!("references unknown capture group {:?}", name);
+            diagnostics.push(Diagnostic::new(path, &message));
+        });
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn create_directory_at_path(path: &Path) -> String {
+        fs::create_dir(path)
+            .expect("Unable to create directory");
+
+        path.to_str()
+            .map(|v| v.to_string())
+            .expect("Unable to build path for directory")
+    }
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn check_configuration_file_without_file() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path()).join("configuration.json");
+
+        let actual = check_configuration_file(path.to_str().unwrap());
+
+        assert_eq!(1, actual.len());
+        assert!(actual[0].message.starts_with("unable to read configuration file"));
+    }
+
+    #[test]
+    fn check_configuration_with_invalid_json() {
+        let configuration: &str = r#"
+        {
+            "source": "/tmp",
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "Unable to parse configuration at line 4, column 9: Unexpected character: }".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, Path::new("/"));
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_invalid_yaml() {
+        let configuration: &str = r#"
+source: /tmp
+targets: [
+"#;
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Yaml, Path::new("/"));
+
+        assert_eq!(1, actual.len());
+        assert!(actual[0].message.starts_with("Unable to parse configuration"));
+    }
+
+    #[test]
+    fn check_configuration_with_unknown_key_in_toml() {
+        let configuration: &str = r#"
+        source = "/tmp"
+        targets = ["/tmp"]
+
+        [[linkMaps]]
+        regex = "leaf"
+        targt = "/tmp"
+        "#;
+        let expected: Vec<String> = vec![
+            "linkMaps[0]: unknown key \"targt\"".to_string(),
+            "linkMaps[0].target: is missing".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Toml, Path::new("/"));
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_empty_object() {
+        let configuration: &str = "{}";
+        let expected: Vec<String> = vec![
+            "source: is missing, either source or sources is required".to_string(),
+            "targets: is missing".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, Path::new("/"));
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_valid_configuration() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = create_directory_at_path(&path.join("source"));
+        let target = create_directory_at_path(&path.join("target"));
+        let configuration = json::stringify(json::object! {
+            "source": source,
+            "targets": [target.clone()],
+            "excludes": ["*.part", { "regex": "^build$", "type": "branch", "scope": "basename" }],
+            "linkMaps": [{ "regex": "(.*)\\.pkg\\.tar\\.zst", "target": target.clone() }],
+        });
+        let expected: Vec<Diagnostic> = Vec::new();
+
+        let actual = check_configuration(&configuration, ConfigurationFormat::Json, Path::new("/"));
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn check_configuration_with_relative_paths() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "targets": ["target", "missing"]
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "targets[1]: directory does not exist".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_every_problem() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = create_directory_at_path(&path.join("source"));
+        let target = create_directory_at_path(&path.join("target"));
+        let missing = path.join("missing").to_str().unwrap().to_string();
+        let configuration = json::stringify(json::object! {
+            "source": source.clone(),
+            "sources": [{ "path": source.clone(), "priority": "high" }, missing.clone()],
+            "targets": [target.clone(), missing],
+            "excludes": [
+                "[",
+                { "glob": "*.part", "regex": ".*\\.part" },
+                { "regex": "(", "type": "directory", "scope": "absolute" },
+            ],
+            "linkMaps": [
+                { "regex": "", "target": target.clone() },
+                { "regex": "(", "targt": target.clone() },
+                { "regex": "leaf", "target": "$LINKER_UNDEFINED_VARIABLE/pkg" },
+                "linkMap",
+            ],
+            "exclude": [],
+        });
+        let expected: Vec<String> = vec![
+            "unknown key \"exclude\"".to_string(),
+            "sources[0].priority: must be an integer".to_string(),
+            "sources[1]: directory does not exist".to_string(),
+            "targets[1]: directory does not exist".to_string(),
+            "excludes[0]: invalid glob: error parsing glob '[': unclosed character class; missing ']'".to_string(),
+            "excludes[1]: must not have both glob and regex".to_string(),
+            "excludes[2].regex: invalid regex: regex parse error:\n    (\n    ^\nerror: unclosed group".to_string(),
+            "excludes[2].type: Invalid exclude type \"directory\", expected leaf, branch or link".to_string(),
+            "excludes[2].scope: Invalid exclude scope \"absolute\", expected basename or relative".to_string(),
+            "linkMaps[0].regex: must not be empty".to_string(),
+            "linkMaps[1]: unknown key \"targt\"".to_string(),
+            "linkMaps[1].regex: invalid regex: regex parse error:\n    (\n    ^\nerror: unclosed group".to_string(),
+            "linkMaps[1].target: is missing".to_string(),
+            "linkMaps[2].target: Environment variable LINKER_UNDEFINED_VARIABLE is not defined, referenced in \"$LINKER_UNDEFINED_VARIABLE/pkg\"".to_string(),
+            "linkMaps[3]: must be an object".to_string(),
+        ];
+
+        let actual = check_configuration(&configuration, ConfigurationFormat::Json, Path::new("/"));
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_regex_matching_invalid_utf8() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = create_directory_at_path(&path.join("source"));
+        let target = create_directory_at_path(&path.join("target"));
+        let configuration = json::stringify(json::object! {
+            "source": source,
+            "targets": [target.clone()],
+            "excludes": [{ "regex": "caf(?-u:\\xe9)" }],
+            "linkMaps": [{ "regex": "(?-u:\\xff)", "target": target.clone() }],
+        });
+        let expected: Vec<String> = Vec::new();
+
+        let actual = check_configuration(&configuration, ConfigurationFormat::Json, Path::new("/"));
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_invalid_max_depth() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        let configuration: &str = r#"
+        {
+            "sources": [{ "path": "source", "maxDepth": -1 }],
+            "targets": [
+                { "path": "target", "maxDepth": 0 },
+                { "path": "target", "maxDepth": "flat" }
+            ]
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "sources[0].maxDepth: must be a non-negative integer".to_string(),
+            "targets[1].maxDepth: must be a non-negative integer".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_target_directory_options() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "targets": [
+                { "path": "target", "directoryMode": "0750", "owner": "0", "group": "root" },
+                { "path": "target", "directoryMode": "rwx", "owner": "linker-undefined-user", "mode": "0750" },
+                1,
+                { "path": "target", "onConflict": "overwrite" }
+            ]
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "targets[1]: unknown key \"mode\"".to_string(),
+            "targets[1].directoryMode: Invalid directory mode \"rwx\", expected an octal mode, e.g. 0755".to_string(),
+            "targets[1].owner: Unknown user \"linker-undefined-user\"".to_string(),
+            "targets[2]: must be a string or an object".to_string(),
+            "targets[3].onConflict: Invalid conflict policy \"overwrite\", expected skip, fail, replace, backup or suffix".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_invalid_match_strategy() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "targets": ["target"],
+            "matchStrategy": "random"
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "matchStrategy: Invalid match strategy \"random\", expected first, last, all or error".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_invalid_link_options() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "targets": ["target"],
+            "relative": "yes",
+            "transactional": "yes",
+            "journal": "",
+            "linkMaps": [
+                { "regex": "leaf", "target": "target", "relative": false },
+                { "regex": "leaf", "target": "target", "relative": 1 },
+                { "regex": "leaf", "target": "target", "mode": "copy,link" }
+            ]
+        }
+        "#;
+        let expected: Vec<String> = vec![
+            "linkMaps[1].relative: must be a boolean".to_string(),
+            "linkMaps[2].mode: Invalid link mode \"link\", expected symlink, hardlink, reflink or copy".to_string(),
+            "relative: must be a boolean".to_string(),
+            "transactional: must be a boolean".to_string(),
+            "journal: must not be empty".to_string(),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_link_map_name() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = create_directory_at_path(&path.join("source"));
+        let target = create_directory_at_path(&path.join("target"));
+        let configuration = json::stringify(json::object! {
+            "source": source,
+            "targets": [target.clone()],
+            "linkMaps": [
+                { "regex": "(?P<name>.*)-x86_64\\.pkg\\.tar\\.zst", "target": target.clone(), "name": "$1-${name}" },
+                { "regex": "(.*)\\.pkg\\.tar\\.zst", "target": target.clone(), "name": "$2-${version}$$" },
+                { "regex": "leaf", "target": target.clone(), "name": "" },
+                { "regex": "(leaf)", "target": target.clone(), "name": "../$1" },
+                { "regex": "leaf", "target": target.clone(), "name": ".." },
+            ],
+        });
+        let expected: Vec<String> = vec![
+            "linkMaps[1].name: references unknown capture group \"2\"".to_string(),
+            "linkMaps[1].name: references unknown capture group \"version\"".to_string(),
+            "linkMaps[2].name: must not be empty".to_string(),
+            "linkMaps[3].name: Invalid link name \"../$1\", must not contain /".to_string(),
+            "linkMaps[4].name: Invalid link name \"..\"".to_string(),
+        ];
+
+        let actual = check_configuration(&configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_includes() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        create_directory_at_path(&path.join("conf.d"));
+        fs::write(path.join("conf.d").join("10-team.json"), r#"
+        {
+            "source": "source",
+            "linkMaps": [
+                {
+                    "regex": "(",
+                    "target": "target"
+                }
+            ]
+        }
+        "#).unwrap();
+        fs::write(path.join("conf.d").join("20-team.toml"), r#"
+        targets = ["missing"]
+        "#).unwrap();
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "targets": ["target"],
+            "include": ["conf.d/*", "missing.json"]
+        }
+        "#;
+        let team_path = path.join("conf.d").join("10-team.json").to_str().unwrap().to_string();
+        let other_team_path = path.join("conf.d").join("20-team.toml").to_str().unwrap().to_string();
+        let missing_path = path.join("missing.json").to_str().unwrap().to_string();
+        let expected: Vec<String> = vec![
+            format!("{}: unknown key \"source\"", team_path),
+            format!("{}:linkMaps[0].regex: invalid regex: regex parse error:\n    (\n    ^\nerror: unclosed group", team_path),
+            format!("{}:targets[0]: directory does not exist", other_team_path),
+            format!("{}: unable to read configuration file: No such file or directory (os error 2)", missing_path),
+        ];
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_with_targets_in_nested_include() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        create_directory_at_path(&path.join("conf.d"));
+        fs::write(path.join("conf.d").join("10-team.json"), r#"
+        {
+            "include": ["20-team.toml"]
+        }
+        "#).unwrap();
+        fs::write(path.join("conf.d").join("20-team.toml"), r#"
+        targets = ["../target"]
+        "#).unwrap();
+        let configuration: &str = r#"
+        {
+            "source": "source",
+            "include": ["conf.d/10-team.json"]
+        }
+        "#;
+        let expected: Vec<String> = Vec::new();
+
+        let actual = check_configuration(configuration, ConfigurationFormat::Json, &path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+
+    #[test]
+    fn check_configuration_file_with_include_cycle() {
+        let directory = create_temporary_directory();
+        let path = fs::canonicalize(directory.path()).unwrap();
+        create_directory_at_path(&path.join("source"));
+        create_directory_at_path(&path.join("target"));
+        fs::write(path.join("team.json"), r#"
+        {
+            "include": ["configuration.json"]
+        }
+        "#).unwrap();
+        fs::write(path.join("configuration.json"), r#"
+        {
+            "source": "source",
+            "targets": ["target"],
+            "include": ["team.json"]
+        }
+        "#).unwrap();
+        let configuration_path = path.join("configuration.json").to_str().unwrap().to_string();
+        let expected: Vec<String> = vec![
+            format!("{}: configuration file includes itself", configuration_path),
+        ];
+
+        let actual = check_configuration_file(&configuration_path);
+
+        assert_eq!(expected, messages(&actual))
+    }
+}
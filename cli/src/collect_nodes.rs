@@ -15,91 +15,243 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::fs::Metadata;
+use std::io;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 
-use log::error;
+use log::warn;
+use rayon::{ThreadPoolBuildError, ThreadPoolBuilder};
+use rayon::prelude::*;
 
 use crate::node::Node;
 
-pub fn collect_nodes(path: &PathBuf) -> Vec<Node> {
-    let directory = fs::read_dir(path);
-    if directory.is_err() {
-        error!("Unable to read \"{:?}\" directory: {:?}", path.to_str().unwrap(), directory.err().unwrap());
-        return Vec::new();
+/// Nodes found while scanning a directory, together with the errors for the entries that were
+/// unable to be scanned.
+#[derive(Default, Eq, PartialEq, Debug)]
+pub struct ScanResult {
+    pub nodes: Vec<Node>,
+    pub errors: Vec<ScanError>,
+    /// Links that were followed, with the path of the link and its resolved path.
+    pub followed_links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ScanResult {
+    fn from_node(node: Node) -> Self {
+        ScanResult { nodes: vec![node], ..ScanResult::default() }
+    }
+
+    fn from_error(error: ScanError) -> Self {
+        ScanResult { errors: vec![error], ..ScanResult::default() }
+    }
+
+    pub(crate) fn extend(&mut self, other: ScanResult) {
+        self.nodes.extend(other.nodes);
+        self.errors.extend(other.errors);
+        self.followed_links.extend(other.followed_links);
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum ScanOperation {
+    ReadDirectory,
+    ReadEntry,
+    ReadMetadata,
+    ReadLink,
+    ResolveLink,
+}
+
+impl Display for ScanOperation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanOperation::ReadDirectory => write!(f, "read directory"),
+            ScanOperation::ReadEntry => write!(f, "read entry in"),
+            ScanOperation::ReadMetadata => write!(f, "read metadata for"),
+            ScanOperation::ReadLink => write!(f, "read link"),
+            ScanOperation::ResolveLink => write!(f, "resolve link"),
+        }
+    }
+}
+
+/// Entry that was unable to be scanned, the entry is left out from the nodes.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ScanError {
+    pub path: PathBuf,
+    pub operation: ScanOperation,
+    pub kind: io::ErrorKind,
+}
+
+impl ScanError {
+    fn new(path: &Path, operation: ScanOperation, kind: io::ErrorKind) -> Self {
+        ScanError { path: path.to_path_buf(), operation, kind }
     }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unable to {} {:?}: {}", self.operation, self.path, self.kind)
+    }
+}
+
+/// Options used while scanning a directory, by default every branch is scanned and links are
+/// collected as links.
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug)]
+pub struct ScanOptions {
+    /// Levels of branches to descend into, `0` only collects the direct children.
+    pub max_depth: Option<usize>,
+    /// Links are followed, i.e. collected as the node of their resolved path but with the path of
+    /// the link, the resolved path is kept with the followed links.
+    pub follow_links: bool,
+}
+
+/// Device and inode of a directory, used to detect links to one of the parent directories.
+type DirectoryId = (u64, u64);
+
+/// Collects every node within the directory, regardless of the depth.
+#[cfg(test)]
+pub fn collect_nodes(path: &Path) -> ScanResult {
+    collect_nodes_with_options(path, ScanOptions::default())
+}
+
+/// Collects the nodes within the directory, branches at the maximum depth are collected without
+/// their children. Branches are scanned in parallel on the rayon thread pool, i.e. the number of
+/// threads is configured with `configure_thread_pool`.
+pub fn collect_nodes_with_options(path: &Path, options: ScanOptions) -> ScanResult {
+    let parents = match fs::metadata(path) {
+        Ok(metadata) if options.follow_links => vec![directory_id(&metadata)],
+        _ => Vec::new(),
+    };
+    scan_directory(path, options, &parents)
+}
+
+/// Configures the number of threads used while scanning directories, `0` uses one thread per CPU.
+pub(crate) fn configure_thread_pool(jobs: usize) -> Result<(), ThreadPoolBuildError> {
+    ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build_global()
+}
+
+fn directory_id(metadata: &Metadata) -> DirectoryId {
+    (metadata.dev(), metadata.ino())
+}
+
+fn scan_directory(path: &Path, options: ScanOptions, parents: &[DirectoryId]) -> ScanResult {
+    let mut result = ScanResult::default();
+    let reader = match fs::read_dir(path) {
+        Ok(reader) => reader,
+        Err(e) => {
+            result.errors.push(ScanError::new(path, ScanOperation::ReadDirectory, e.kind()));
+            return result;
+        }
+    };
 
     let mut entries: Vec<PathBuf> = Vec::new();
-    if let Ok(reader) = directory {
-        for result in reader {
-            if result.is_err() {
-                error!("Unable to handle entry: {:?}", result.err().unwrap());
-                continue;
-            }
-
-            let entry = result.unwrap();
-            entries.push(entry.path());
+    for entry in reader {
+        match entry {
+            Ok(entry) => entries.push(entry.path()),
+            Err(e) => result.errors.push(ScanError::new(path, ScanOperation::ReadEntry, e.kind())),
         }
     }
-    return collect_and_transform_nodes(entries);
+    entries.par_iter()
+        .map(|v| transform_to_node(v, options, parents))
+        .collect::<Vec<ScanResult>>()
+        .into_iter()
+        .for_each(|v| result.extend(v));
+    result.nodes.sort();
+
+    result
+}
+
+fn transform_to_node(path: &Path, options: ScanOptions, parents: &[DirectoryId]) -> ScanResult {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return ScanResult::from_error(ScanError::new(path, ScanOperation::ReadMetadata, e.kind())),
+    };
+
+    if metadata.is_symlink() && options.follow_links {
+        follow_link(path, options, parents)
+    } else if metadata.is_symlink() {
+        transform_link(path)
+    } else if metadata.is_dir() {
+        transform_branch(path, &metadata, options, parents)
+    } else {
+        ScanResult::from_node(Node::Leaf(path.to_path_buf()))
+    }
 }
 
-fn collect_and_transform_nodes(entries: Vec<PathBuf>) -> Vec<Node> {
-    let mut nodes = entries.iter()
-        .map(|v| transform_to_node(v, v.to_str()))
-        .filter(|v| v.is_some())
-        .map(|v| v.unwrap())
-        .collect::<Vec<Node>>();
-    nodes.sort();
+fn transform_branch(path: &Path, metadata: &Metadata, options: ScanOptions, parents: &[DirectoryId]) -> ScanResult {
+    if options.max_depth == Some(0) {
+        return ScanResult::from_node(Node::Branch(path.to_path_buf(), Vec::new()));
+    }
 
-    return nodes;
+    let mut parents = parents.to_vec();
+    if options.follow_links {
+        parents.push(directory_id(metadata));
+    }
+    let options = ScanOptions { max_depth: options.max_depth.map(|v| v - 1), ..options };
+    let branch = scan_directory(path, options, &parents);
+    ScanResult { nodes: vec![Node::Branch(path.to_path_buf(), branch.nodes)], ..branch }
 }
 
-fn transform_to_node(path: &PathBuf, s: Option<&str>) -> Option<Node> {
-    match s {
-        Some(v) => {
-            let file_type = fs::symlink_metadata(path)
-                .expect(&format!("Unable to read metadata on {:?}", path.to_str()))
-                .file_type();
-
-            let node = if file_type.is_symlink() {
-                Node::Link(v.to_string(), normalize_link_source(v))
-            } else if file_type.is_dir() {
-                Node::Branch(v.to_string(), collect_nodes(path))
+/// Collects the node of the resolved path of the link, using the path of the link so that the node
+/// stays within the source. Links that can't be resolved, or that link to one of their parent
+/// directories, are collected as links.
+fn follow_link(path: &Path, options: ScanOptions, parents: &[DirectoryId]) -> ScanResult {
+    let resolved = fs::canonicalize(path)
+        .and_then(|source| fs::metadata(&source).map(|metadata| (source, metadata)));
+    match resolved {
+        Ok((_, metadata)) if metadata.is_dir() && parents.contains(&directory_id(&metadata)) => {
+            warn!("{:?} links to one of its parent directories, the link is not followed", path);
+            transform_link(path)
+        }
+        Ok((source, metadata)) => {
+            let mut result = if metadata.is_dir() {
+                transform_branch(path, &metadata, options, parents)
             } else {
-                Node::Leaf(v.to_string())
+                ScanResult::from_node(Node::Leaf(path.to_path_buf()))
             };
-            Some(node)
+            result.followed_links.push((path.to_path_buf(), source));
+            result
         }
-        None => None
+        Err(_) => transform_link(path),
     }
 }
 
-fn normalize_link_source(v: &str) -> String {
-    let source = fs::read_link(v)
-        .map(|s| s.to_str()
-            .expect(&format!("Unable to transform link to str: {:?}", v))
-            .to_owned())
-        .expect(&format!("Unable to read link: {:?}", v));
-
-    let canonical_path = fs::canonicalize(
-        Path::new(v)
-            .parent()
-            .map(|p| p.to_owned())
-            .map(|p| p.join(source))
-            .expect(&format!("Unable to build parent path for: {:?}", v))
-    );
-
-    canonical_path.expect(&format!("Unable to read canonical path: {:?}", v))
-        .to_str()
-        .expect(&format!("Unable to transform canonical path to str: {:?}", v))
-        .to_string()
+/// Transforms the link into a `Node::BrokenLink` when the link is dangling, otherwise into a
+/// `Node::Link` with the canonical source path. Any other error while resolving the source, e.g.
+/// a permission or I/O error, is a scan error since the link can't be told apart from a valid one.
+fn transform_link(path: &Path) -> ScanResult {
+    let source = match fs::read_link(path) {
+        Ok(source) => source,
+        Err(e) => return ScanResult::from_error(ScanError::new(path, ScanOperation::ReadLink, e.kind())),
+    };
+    match normalize_link_source(path, &source) {
+        Ok(canonical_path) => ScanResult::from_node(Node::Link(path.to_path_buf(), canonical_path)),
+        Err(e) if is_dangling(&e) => ScanResult::from_node(Node::BrokenLink(path.to_path_buf(), source)),
+        Err(e) => ScanResult::from_error(ScanError::new(path, ScanOperation::ResolveLink, e.kind())),
+    }
+}
+
+fn normalize_link_source(path: &Path, source: &Path) -> io::Result<PathBuf> {
+    let parent = path.parent().unwrap_or(Path::new("/"));
+
+    fs::canonicalize(parent.join(source))
+}
+
+/// The source of a dangling link, or one of the directories leading to it, doesn't exist.
+fn is_dangling(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory)
 }
 
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsStr;
     use std::fs::File;
+    use std::os::unix::ffi::OsStrExt;
     use std::os::unix::fs as unix_fs;
     use std::path::Path;
 
@@ -112,31 +264,24 @@ mod tests {
             .expect("Unable to create temporary directory")
     }
 
-    fn create_file(path: &PathBuf) -> String {
+    fn create_file(path: &PathBuf) -> PathBuf {
         File::create(path)
             .expect(&format!("Unable to create file at: {:?}", path.to_str()));
 
-        path.to_str()
-            .map(|v| v.to_string())
-            .expect(&format!("Unable to build path for file at: {:?}", path.to_str()))
+        path.to_owned()
     }
 
-    fn create_link(original: &str, link: &PathBuf) -> String {
-        let path = link.to_str()
-            .expect(&format!("Unable to build path for link at: {:?}", link.to_str()));
-
-        unix_fs::symlink(original.to_string(), path.to_string())
-            .expect(&format!("Unable to create symlink for {:?} -> {:?}", link, original));
-        return path.to_string();
+    fn create_link<P: AsRef<Path>>(original: P, link: &PathBuf) -> PathBuf {
+        unix_fs::symlink(original.as_ref(), link)
+            .expect(&format!("Unable to create symlink for {:?} -> {:?}", link, original.as_ref()));
+        return link.to_owned();
     }
 
-    fn create_directory_at_path(path: &Path) -> String {
+    fn create_directory_at_path(path: &Path) -> PathBuf {
         fs::create_dir(path)
             .expect("Unable to create directory");
 
-        path.to_str()
-            .map(|v| v.to_string())
-            .expect("Unable to build path for directory")
+        path.to_path_buf()
     }
 
     #[test]
@@ -144,7 +289,25 @@ mod tests {
         let path = PathBuf::from("/tmp/should-not-exists");
         let expected: Vec<Node> = Vec::new();
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_scan_error() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let leaf = create_file(&path.join("leaf"));
+        let expected = ScanResult {
+            nodes: Vec::new(),
+            errors: vec![
+                ScanError::new(&leaf, ScanOperation::ReadDirectory, io::ErrorKind::NotADirectory),
+            ],
+            followed_links: Vec::new(),
+        };
+
+        let actual = collect_nodes(&leaf);
 
         assert_eq!(expected, actual)
     }
@@ -155,7 +318,7 @@ mod tests {
         let path = PathBuf::from(directory.path());
         let expected: Vec<Node> = Vec::new();
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -168,7 +331,7 @@ mod tests {
             Node::Leaf(create_file(&path.join("leaf")))
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -182,7 +345,20 @@ mod tests {
             Node::Leaf(create_file(&path.join("leaf-2")))
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_non_utf8_leaf() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let expected = vec![
+            Node::Leaf(create_file(&path.join(OsStr::from_bytes(b"caf\xe9.flac"))))
+        ];
+
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -197,11 +373,75 @@ mod tests {
             Node::Link(create_link(&original, &path.join("link")), original),
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_relative_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        create_directory_at_path(&targets_path);
+        let original = create_file(&sources_path.join("original"));
+        let expected = vec![
+            Node::Link(create_link("../sources/original", &targets_path.join("link")), original),
+        ];
+
+        let actual = collect_nodes(&targets_path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_broken_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let original = create_file(&path.join("original"));
+        let expected = vec![
+            Node::Leaf(original.clone()),
+            Node::Link(create_link(&original, &path.join("link")), original),
+            Node::BrokenLink(create_link("missing", &path.join("broken")), PathBuf::from("missing")),
+        ];
+
+        let actual = collect_nodes(&path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_broken_link_through_leaf() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let leaf = create_file(&path.join("leaf"));
+        let expected = vec![
+            Node::Leaf(leaf),
+            Node::BrokenLink(create_link("leaf/missing", &path.join("broken")), PathBuf::from("leaf/missing")),
+        ];
+
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
 
+    #[test]
+    fn collect_nodes_with_link_loop() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link = create_link("loop", &path.join("loop"));
+        let expected = (Vec::<Node>::new(), vec![(link, ScanOperation::ResolveLink)]);
+
+        let actual = collect_nodes(&path);
+
+        let errors = actual.errors.into_iter()
+            .map(|v| (v.path, v.operation))
+            .collect::<Vec<(PathBuf, ScanOperation)>>();
+        assert_eq!(expected, (actual.nodes, errors))
+    }
+
     #[test]
     fn collect_nodes_with_links() {
         let directory = create_temporary_directory();
@@ -215,7 +455,7 @@ mod tests {
             Node::Link(create_link(&second_leaf, &path.join("link-2")), second_leaf),
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -231,7 +471,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -251,7 +491,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -270,7 +510,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -296,7 +536,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -318,7 +558,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -350,7 +590,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -370,7 +610,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -398,7 +638,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -424,7 +664,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -464,7 +704,7 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
 
         assert_eq!(expected, actual)
     }
@@ -502,7 +742,123 @@ mod tests {
             )
         ];
 
-        let actual = collect_nodes(&path);
+        let actual = collect_nodes(&path).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_options_with_max_depth() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let branch = create_directory_at_path(&path.join("branch"));
+        let nested = create_directory_at_path(&path.join("branch").join("nested"));
+        create_file(&path.join("branch").join("nested").join("leaf"));
+        let expected = vec![
+            Node::Leaf(create_file(&path.join("leaf"))),
+            Node::Branch(
+                branch,
+                vec![
+                    Node::Leaf(create_file(&path.join("branch").join("leaf"))),
+                    Node::Branch(nested, vec![]),
+                ],
+            ),
+        ];
+        let options = ScanOptions { max_depth: Some(1), ..ScanOptions::default() };
+
+        let actual = collect_nodes_with_options(&path, options).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_options_with_zero_max_depth() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let branch = create_directory_at_path(&path.join("branch"));
+        create_file(&path.join("branch").join("leaf"));
+        let expected = vec![
+            Node::Leaf(create_file(&path.join("leaf"))),
+            Node::Branch(branch, vec![]),
+        ];
+        let options = ScanOptions { max_depth: Some(0), ..ScanOptions::default() };
+
+        let actual = collect_nodes_with_options(&path, options).nodes;
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_options_with_followed_links() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = create_directory_at_path(&path.join("source"));
+        let branch = create_directory_at_path(&path.join("source").join("branch"));
+        create_directory_at_path(&path.join("outside"));
+        let outside_branch = create_directory_at_path(&path.join("outside").join("branch"));
+        let leaf = create_file(&path.join("outside").join("leaf"));
+        create_file(&path.join("outside").join("branch").join("leaf"));
+        let linked_branch = create_link(&outside_branch, &path.join("source").join("linked-branch"));
+        let linked_leaf = create_link(&leaf, &path.join("source").join("linked-leaf"));
+        let parent_link = create_link("..", &path.join("source").join("branch").join("parent"));
+        let expected = ScanResult {
+            nodes: vec![
+                Node::Leaf(linked_leaf.clone()),
+                Node::Branch(
+                    branch,
+                    vec![
+                        Node::Link(parent_link, source.clone())
+                    ],
+                ),
+                Node::Branch(
+                    linked_branch.clone(),
+                    vec![
+                        Node::Leaf(linked_branch.join("leaf"))
+                    ],
+                ),
+            ],
+            errors: Vec::new(),
+            followed_links: vec![
+                (linked_branch, outside_branch),
+                (linked_leaf, leaf),
+            ],
+        };
+        let options = ScanOptions { follow_links: true, ..ScanOptions::default() };
+
+        let mut actual = collect_nodes_with_options(&source, options);
+        actual.followed_links.sort();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn collect_nodes_with_thread_pool() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let branch = create_directory_at_path(&path.join("branch"));
+        let nested = create_directory_at_path(&path.join("branch").join("nested"));
+        let expected = vec![
+            Node::Leaf(create_file(&path.join("leaf"))),
+            Node::Branch(
+                branch,
+                vec![
+                    Node::Leaf(create_file(&path.join("branch").join("leaf-1"))),
+                    Node::Leaf(create_file(&path.join("branch").join("leaf-2"))),
+                    Node::Branch(
+                        nested,
+                        vec![
+                            Node::Leaf(create_file(&path.join("branch").join("nested").join("leaf")))
+                        ],
+                    ),
+                ],
+            ),
+        ];
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .expect("Unable to build thread pool");
+
+        let actual = pool.install(|| collect_nodes(&path)).nodes;
 
         assert_eq!(expected, actual)
     }
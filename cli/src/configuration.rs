@@ -15,82 +15,653 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::error::Error;
+use std::ffi::{OsStr, OsString};
+use std::fmt::{Display, Formatter};
 use std::fs;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
+use globset::{GlobBuilder, GlobMatcher};
 use json::JsonValue;
-use regex::Regex;
+use nix::unistd::{Group, User};
+use regex::bytes::Regex;
+
+use crate::configuration_error::ConfigurationError;
+use crate::configuration_format::{ConfigurationFormat, parse_document};
+use crate::expand_path::expand_path;
 
 #[derive(Default, Eq, PartialEq, Clone, Debug)]
 pub struct Configuration {
-    pub source: Option<String>,
-    pub targets: Vec<String>,
-    pub excludes: Vec<String>,
+    pub sources: Vec<Source>,
+    pub targets: Vec<Target>,
+    pub excludes: Vec<Exclude>,
     pub link_maps: Vec<LinkMap>,
+    pub match_strategy: MatchStrategy,
+    pub link_options: LinkOptions,
+    pub journal: JournalOptions,
+    /// Links within the sources are followed, i.e. symlinked directories are scanned and symlinked
+    /// files are linked to their resolved path.
+    pub follow_source_links: bool,
+}
+
+/// Default location of the journals, expanded like any other path within the configuration.
+const DEFAULT_JOURNAL_DIRECTORY: &str = "~/.local/state/linker";
+
+/// In a transactional run every change to the targets is written to a journal within the
+/// directory, and the changes are rolled back if any of the links can't be created.
+#[derive(Default, Eq, PartialEq, Clone, Debug)]
+pub struct JournalOptions {
+    pub transactional: bool,
+    pub directory: Option<String>,
+}
+
+impl JournalOptions {
+    /// Runs are only written to the journal when transactional, or when a directory is configured.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.transactional || self.directory.is_some()
+    }
+
+    /// Directory of the journals, defaults to `~/.local/state/linker`.
+    pub(crate) fn resolve_directory(&self) -> Result<String, ConfigurationError> {
+        match &self.directory {
+            Some(directory) => Ok(directory.to_owned()),
+            None => expand_path(DEFAULT_JOURNAL_DIRECTORY, Path::new("/")),
+        }
+    }
+}
+
+/// Options for how links are created, configured for every link map and optionally overridden
+/// by each link map.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct LinkOptions {
+    pub relative: bool,
+    pub modes: Vec<LinkMode>,
+    pub directory: DirectoryOptions,
+    pub on_conflict: ConflictPolicy,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        LinkOptions {
+            relative: false,
+            modes: vec![LinkMode::Symlink],
+            directory: DirectoryOptions::default(),
+            on_conflict: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// How the link is created, when more than one mode is configured the modes are attempted in
+/// order until one of them succeeds, e.g. a reflink is not possible across file systems.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LinkMode {
+    Symlink,
+    Hardlink,
+    Reflink,
+    Copy,
+}
+
+impl LinkMode {
+    /// Parses a fallback chain of modes, e.g. `reflink,hardlink,copy`.
+    pub(crate) fn parse_modes(s: &str) -> Result<Vec<LinkMode>, String> {
+        s.split(',')
+            .map(|v| LinkMode::from_str(v.trim()))
+            .collect()
+    }
+}
+
+impl FromStr for LinkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "symlink" => Ok(LinkMode::Symlink),
+            "hardlink" => Ok(LinkMode::Hardlink),
+            "reflink" => Ok(LinkMode::Reflink),
+            "copy" => Ok(LinkMode::Copy),
+            _ => Err(format!("Invalid link mode {:?}, expected symlink, hardlink, reflink or copy", s)),
+        }
+    }
+}
+
+impl Display for LinkMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkMode::Symlink => write!(f, "symlink"),
+            LinkMode::Hardlink => write!(f, "hardlink"),
+            LinkMode::Reflink => write!(f, "reflink"),
+            LinkMode::Copy => write!(f, "copy"),
+        }
+    }
+}
+
+/// Decides which link maps are used when more than one link map matches the same node.
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum MatchStrategy {
+    First,
+    #[default]
+    Last,
+    All,
+    Error,
+}
+
+impl FromStr for MatchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(MatchStrategy::First),
+            "last" => Ok(MatchStrategy::Last),
+            "all" => Ok(MatchStrategy::All),
+            "error" => Ok(MatchStrategy::Error),
+            _ => Err(format!("Invalid match strategy {:?}, expected first, last, all or error", s)),
+        }
+    }
+}
+
+/// Decides what happens when something already exists at the path of the link, e.g. a file or a
+/// link to another source.
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ConflictPolicy {
+    #[default]
+    Skip,
+    Fail,
+    Replace,
+    Backup,
+    Suffix,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(ConflictPolicy::Skip),
+            "fail" => Ok(ConflictPolicy::Fail),
+            "replace" => Ok(ConflictPolicy::Replace),
+            "backup" => Ok(ConflictPolicy::Backup),
+            "suffix" => Ok(ConflictPolicy::Suffix),
+            _ => Err(format!("Invalid conflict policy {:?}, expected skip, fail, replace, backup or suffix", s)),
+        }
+    }
+}
+
+/// Sources are ordered by precedence, i.e. when the same basename exists in more than one source
+/// the node from the source with the highest priority is linked.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Source {
+    pub path: String,
+    pub priority: i64,
+    /// Levels of branches to scan below the path, `0` only scans the direct children.
+    pub max_depth: Option<usize>,
+}
+
+impl Source {
+    pub(crate) fn new(path: String, priority: i64) -> Self {
+        Source { path, priority, max_depth: None }
+    }
+
+    pub(crate) fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Source { max_depth, ..self }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Target {
+    pub path: String,
+    pub directory: DirectoryOptions,
+    pub on_conflict: ConflictPolicy,
+    /// Levels of branches to scan below the path, `0` only scans the direct children.
+    pub max_depth: Option<usize>,
+}
+
+impl Target {
+    pub(crate) fn new(path: String) -> Self {
+        Target {
+            path,
+            directory: DirectoryOptions::default(),
+            on_conflict: ConflictPolicy::default(),
+            max_depth: None,
+        }
+    }
+
+    pub(crate) fn with_directory(self, directory: DirectoryOptions) -> Self {
+        Target { directory, ..self }
+    }
+
+    pub(crate) fn with_on_conflict(self, on_conflict: ConflictPolicy) -> Self {
+        Target { on_conflict, ..self }
+    }
+
+    pub(crate) fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Target { max_depth, ..self }
+    }
+}
+
+/// Mode and ownership applied to the directories created by linker within a target, anything not
+/// set is left to the defaults of the system, e.g. the umask and the current user.
+#[derive(Default, Eq, PartialEq, Copy, Clone, Debug)]
+pub struct DirectoryOptions {
+    pub mode: Option<u32>,
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
 }
 
 #[derive(Clone, Debug)]
 pub struct LinkMap {
     regex: Regex,
     pub target: String,
+    name: Option<String>,
+    relative: Option<bool>,
+    modes: Option<Vec<LinkMode>>,
 }
 
 impl PartialEq for LinkMap {
     fn eq(&self, other: &Self) -> bool {
-        self.regex.to_string() == other.regex.to_string() && self.target == other.target
+        self.regex.to_string() == other.regex.to_string()
+            && self.target == other.target
+            && self.name == other.name
+            && self.relative == other.relative
+            && self.modes == other.modes
     }
 }
 
 impl Eq for LinkMap {}
 
 impl LinkMap {
-    pub(crate) fn new(pattern: String, target: String) -> Result<Self, Box<dyn Error>> {
+    pub(crate) fn new(pattern: String, target: String) -> Result<Self, regex::Error> {
         Ok(
             LinkMap {
                 regex: Regex::from_str(pattern.as_str())?,
                 target,
+                name: None,
+                relative: None,
+                modes: None,
             }
         )
     }
 
-    pub(crate) fn is_match(&self, basename: &str) -> bool {
-        self.regex.is_match(basename)
+    /// Template for the name of the link, expanded from the captures of the regex, e.g.
+    /// `$1.pkg.tar.zst` or `${name}-latest`.
+    pub(crate) fn with_name(self, name: String) -> Self {
+        LinkMap { name: Some(name), ..self }
+    }
+
+    pub(crate) fn with_relative(self, relative: bool) -> Self {
+        LinkMap { relative: Some(relative), ..self }
+    }
+
+    pub(crate) fn with_modes(self, modes: Vec<LinkMode>) -> Self {
+        LinkMap { modes: Some(modes), ..self }
+    }
+
+    /// Options used when creating links for the link map, falling back to the options from the
+    /// configuration for anything not set on the link map.
+    pub(crate) fn link_options(&self, defaults: &LinkOptions) -> LinkOptions {
+        LinkOptions {
+            relative: self.relative.unwrap_or(defaults.relative),
+            modes: self.modes.clone().unwrap_or_else(|| defaults.modes.clone()),
+            directory: defaults.directory,
+            on_conflict: defaults.on_conflict,
+        }
+    }
+
+    pub(crate) fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// The regex is matched against the raw bytes of the basename, i.e. basenames that aren't
+    /// valid UTF-8 can still be matched.
+    pub(crate) fn is_match(&self, basename: &OsStr) -> bool {
+        self.regex.is_match(basename.as_bytes())
+    }
+
+    /// Builds the name of the link for the basename, without a name template the basename is used.
+    /// A template that expands to an empty name, `.` or `..` has no valid name for the basename.
+    pub(crate) fn link_name(&self, basename: &OsStr) -> Option<OsString> {
+        let name = match &self.name {
+            Some(name) => name,
+            None => return Some(basename.to_os_string()),
+        };
+
+        let captures = self.regex.captures(basename.as_bytes())?;
+        let mut expanded = Vec::new();
+        captures.expand(name.as_bytes(), &mut expanded);
+        if expanded.is_empty() || validate_link_name(&expanded).is_err() {
+            return None;
+        }
+
+        Some(OsString::from_vec(expanded))
     }
 }
 
-pub fn read_configuration(path: &str) -> Configuration {
-    let data = fs::read_to_string(path)
-        .expect(&format!("Unable to read configuration file at path {}", path).as_str());
+#[derive(Clone, Debug)]
+pub struct Exclude {
+    pattern: ExcludePattern,
+    node_type: Option<ExcludeType>,
+    scope: Option<ExcludeScope>,
+}
+
+#[derive(Clone, Debug)]
+enum ExcludePattern {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ExcludeType {
+    Leaf,
+    Branch,
+    Link,
+}
+
+impl FromStr for ExcludeType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "leaf" => Ok(ExcludeType::Leaf),
+            "branch" => Ok(ExcludeType::Branch),
+            "link" => Ok(ExcludeType::Link),
+            _ => Err(format!("Invalid exclude type {:?}, expected leaf, branch or link", s)),
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ExcludeScope {
+    Basename,
+    Relative,
+}
+
+impl FromStr for ExcludeScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "basename" => Ok(ExcludeScope::Basename),
+            "relative" => Ok(ExcludeScope::Relative),
+            _ => Err(format!("Invalid exclude scope {:?}, expected basename or relative", s)),
+        }
+    }
+}
+
+impl PartialEq for ExcludePattern {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ExcludePattern::Glob(lhs), ExcludePattern::Glob(rhs)) => lhs.glob() == rhs.glob(),
+            (ExcludePattern::Regex(lhs), ExcludePattern::Regex(rhs)) => lhs.as_str() == rhs.as_str(),
+            _ => false
+        }
+    }
+}
+
+impl PartialEq for Exclude {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.node_type == other.node_type && self.scope == other.scope
+    }
+}
+
+impl Eq for Exclude {}
+
+impl Exclude {
+    pub(crate) fn new(pattern: String) -> Result<Self, globset::Error> {
+        Exclude::from_glob(pattern, None, None)
+    }
+
+    /// Glob patterns are matched case insensitive, and a `*` will not match across a path
+    /// separator, use `**` to match across multiple directories.
+    pub(crate) fn from_glob(
+        pattern: String,
+        node_type: Option<ExcludeType>,
+        scope: Option<ExcludeScope>,
+    ) -> Result<Self, globset::Error> {
+        let glob = GlobBuilder::new(pattern.as_str())
+            .case_insensitive(true)
+            .literal_separator(true)
+            .build()?;
+
+        Ok(
+            Exclude {
+                pattern: ExcludePattern::Glob(glob.compile_matcher()),
+                node_type,
+                scope,
+            }
+        )
+    }
+
+    pub(crate) fn from_regex(
+        pattern: String,
+        node_type: Option<ExcludeType>,
+        scope: Option<ExcludeScope>,
+    ) -> Result<Self, regex::Error> {
+        Ok(
+            Exclude {
+                pattern: ExcludePattern::Regex(Regex::from_str(pattern.as_str())?),
+                node_type,
+                scope,
+            }
+        )
+    }
+
+    /// Without a type the exclude applies to every type of node, and without a scope the
+    /// pattern is matched against both the basename and the relative path.
+    pub(crate) fn is_match(&self, node_type: ExcludeType, basename: &OsStr, relative_path: &Path) -> bool {
+        if self.node_type.is_some_and(|v| v != node_type) {
+            return false;
+        }
 
-    let configuration = parse_configuration(data.as_str());
-    if configuration.source.is_none() {
-        panic!("Configuration is missing valid source")
+        match self.scope {
+            Some(ExcludeScope::Basename) => self.is_pattern_match(basename),
+            Some(ExcludeScope::Relative) => self.is_pattern_match(relative_path.as_os_str()),
+            None => self.is_pattern_match(basename) || self.is_pattern_match(relative_path.as_os_str()),
+        }
+    }
+
+    fn is_pattern_match(&self, value: &OsStr) -> bool {
+        match &self.pattern {
+            ExcludePattern::Glob(glob) => glob.is_match(value),
+            ExcludePattern::Regex(regex) => regex.is_match(value.as_bytes()),
+        }
+    }
+}
+
+pub fn read_configuration(path: &str) -> Result<Configuration, ConfigurationError> {
+    let (configuration, includes) = read_configuration_file(path)?;
+    let mut parents = vec![canonicalize_configuration_path(path)];
+    let configuration = merge_includes(configuration, &includes, &configuration_directory(path), &mut parents)?;
+    if configuration.sources.is_empty() {
+        return Err(ConfigurationError::MissingSource);
     }
     if configuration.targets.is_empty() {
-        panic!("Configuration is missing valid targets")
+        return Err(ConfigurationError::MissingTargets);
+    }
+
+    Ok(configuration)
+}
+
+fn read_configuration_file(path: &str) -> Result<(Configuration, Vec<String>), ConfigurationError> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| ConfigurationError::Io(path.to_string(), e))?;
+    if data.is_empty() {
+        return Ok(Default::default());
+    }
+
+    let document = parse_document(ConfigurationFormat::from_path(path), &data)?;
+    let configuration = expand_configuration_paths(map_configuration(&document)?, &configuration_directory(path))?;
+
+    Ok((configuration, map_includes(&document)))
+}
+
+/// Merges the included configuration files, an included file can include other files which are
+/// merged right after the file including them. A file that includes itself, directly or through
+/// one of the files it includes, is an error since it would be included forever.
+fn merge_includes(
+    mut configuration: Configuration,
+    includes: &[String],
+    directory: &Path,
+    parents: &mut Vec<PathBuf>,
+) -> Result<Configuration, ConfigurationError> {
+    for include in resolve_includes(includes, directory)? {
+        let map_err = |e| ConfigurationError::Include(include.to_string(), Box::new(e));
+        let path = canonicalize_configuration_path(&include);
+        if parents.contains(&path) {
+            return Err(map_err(ConfigurationError::IncludeCycle(include.to_string())));
+        }
+
+        let (included, nested_includes) = read_configuration_file(&include).map_err(map_err)?;
+        configuration = merge_configuration(configuration, included);
+        parents.push(path);
+        configuration = merge_includes(configuration, &nested_includes, &configuration_directory(&include), parents)
+            .map_err(map_err)?;
+        parents.pop();
+    }
+
+    Ok(configuration)
+}
+
+pub(crate) fn canonicalize_configuration_path(path: &str) -> PathBuf {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| PathBuf::from(path))
+}
+
+/// Included configuration files are merged in the order of the `include` entries, and files
+/// matching a glob pattern are merged in lexicographical order. Only `targets`, `excludes` and
+/// `linkMaps` are merged, and the link maps of included files are appended. With the `last` match
+/// strategy the link maps from files merged later take precedence, with `first` the earlier ones,
+/// and with `all` every matching link map is used.
+fn merge_configuration(configuration: Configuration, included: Configuration) -> Configuration {
+    let mut targets = configuration.targets;
+    included.targets.into_iter()
+        .for_each(|v| {
+            if !targets.iter().any(|target| target.path == v.path) {
+                targets.push(v);
+            }
+        });
+
+    Configuration {
+        sources: configuration.sources,
+        targets,
+        excludes: [configuration.excludes, included.excludes].concat(),
+        link_maps: [configuration.link_maps, included.link_maps].concat(),
+        match_strategy: configuration.match_strategy,
+        link_options: configuration.link_options,
+        journal: configuration.journal,
+        follow_source_links: configuration.follow_source_links,
+    }
+}
+
+fn map_includes(data: &JsonValue) -> Vec<String> {
+    data["include"].members()
+        .filter_map(|v| v.as_str())
+        .filter_map(map_valid_source)
+        .collect()
+}
+
+/// Resolves the paths for included configuration files, each include can either be a path to a
+/// single configuration file or a glob pattern, e.g. `/etc/linker/conf.d/*.json`.
+pub(crate) fn resolve_includes(includes: &[String], directory: &Path) -> Result<Vec<String>, ConfigurationError> {
+    let mut paths: Vec<String> = Vec::new();
+    for include in includes {
+        paths.extend(resolve_include(include, directory)?);
     }
 
-    configuration
+    Ok(paths)
 }
 
-fn parse_configuration(configuration: &str) -> Configuration {
-    if configuration.is_empty() {
-        return Default::default();
+pub(crate) fn resolve_include(include: &str, directory: &Path) -> Result<Vec<String>, ConfigurationError> {
+    let pattern = expand_path(include, directory)?;
+    if !pattern.contains(['*', '?', '[']) {
+        return Ok(vec![pattern]);
     }
 
-    let data = json::parse(configuration).unwrap();
-    return Configuration {
-        source: map_source(&data),
-        targets: map_targets(&data),
-        excludes: map_excludes(&data),
-        link_maps: map_link_maps(&data),
-    };
+    let mut paths = glob::glob(&pattern)
+        .map_err(|e| ConfigurationError::InvalidInclude(include.to_string(), e.to_string()))?
+        .map(|v| {
+            v.map_err(|e| ConfigurationError::Io(e.path().to_string_lossy().to_string(), e.into()))
+                .and_then(|p| {
+                    p.to_str()
+                        .map(|s| s.to_string())
+                        .ok_or_else(|| {
+                            let message = format!("unable to transform path {:?} to string", p);
+                            ConfigurationError::InvalidInclude(include.to_string(), message)
+                        })
+                })
+        })
+        .collect::<Result<Vec<String>, ConfigurationError>>()?;
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Resolves the directory containing the configuration file, against which relative paths within
+/// the configuration are resolved.
+pub(crate) fn configuration_directory(path: &str) -> PathBuf {
+    fs::canonicalize(path)
+        .ok()
+        .and_then(|v| v.parent().map(|p| p.to_path_buf()))
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+fn expand_configuration_paths(configuration: Configuration, directory: &Path) -> Result<Configuration, ConfigurationError> {
+    Ok(
+        Configuration {
+            sources: configuration.sources.into_iter()
+                .map(|v| Ok(Source { path: expand_path(&v.path, directory)?, ..v }))
+                .collect::<Result<Vec<Source>, ConfigurationError>>()?,
+            targets: configuration.targets.into_iter()
+                .map(|v| Ok(Target { path: expand_path(&v.path, directory)?, ..v }))
+                .collect::<Result<Vec<Target>, ConfigurationError>>()?,
+            excludes: configuration.excludes,
+            link_maps: configuration.link_maps.into_iter()
+                .map(|v| {
+                    let target = expand_path(&v.target, directory)?;
+                    Ok(LinkMap { target, ..v })
+                })
+                .collect::<Result<Vec<LinkMap>, ConfigurationError>>()?,
+            match_strategy: configuration.match_strategy,
+            link_options: configuration.link_options,
+            journal: JournalOptions {
+                directory: configuration.journal.directory
+                    .map(|v| expand_path(&v, directory))
+                    .transpose()?,
+                ..configuration.journal
+            },
+            follow_source_links: configuration.follow_source_links,
+        }
+    )
+}
+
+fn map_configuration(data: &JsonValue) -> Result<Configuration, ConfigurationError> {
+    Ok(
+        Configuration {
+            sources: map_sources(data),
+            targets: map_targets(data)?,
+            excludes: map_excludes(data)?,
+            link_maps: map_link_maps(data)?,
+            match_strategy: map_match_strategy(data)?,
+            link_options: map_link_options(data),
+            journal: map_journal_options(data),
+            follow_source_links: data["followSourceLinks"].as_bool().unwrap_or_default(),
+        }
+    )
 }
 
-fn map_source(data: &JsonValue) -> Option<String> {
+/// The `source` key is kept for backward compatibility and is treated as the first source, with
+/// the default priority. Sources with equal priority keep the order from the configuration.
+fn map_sources(data: &JsonValue) -> Vec<Source> {
+    let mut sources: Vec<Source> = map_source(data).into_iter()
+        .chain(map_valid_sources(&data["sources"]))
+        .collect();
+    sources.sort_by_key(|v| std::cmp::Reverse(v.priority));
+    sources
+}
+
+fn map_source(data: &JsonValue) -> Option<Source> {
     data["source"].as_str()
-        .map_or(None, map_valid_source)
+        .and_then(map_valid_source)
+        .map(|v| Source::new(v, 0))
 }
 
 fn map_valid_source(v: &str) -> Option<String> {
@@ -101,168 +672,1115 @@ fn map_valid_source(v: &str) -> Option<String> {
     }
 }
 
-fn map_targets(data: &JsonValue) -> Vec<String> {
+fn map_valid_sources(data: &JsonValue) -> Vec<Source> {
+    data.members()
+        .filter_map(|v| {
+            if v.is_string() {
+                return v.as_str()
+                    .and_then(map_valid_source)
+                    .map(|path| Source::new(path, 0));
+            }
+
+            v["path"].as_str()
+                .and_then(map_valid_source)
+                .map(|path| {
+                    Source::new(path, v["priority"].as_i64().unwrap_or_default())
+                        .with_max_depth(v["maxDepth"].as_usize())
+                })
+        })
+        .collect()
+}
+
+fn map_targets(data: &JsonValue) -> Result<Vec<Target>, ConfigurationError> {
     match data["targets"] {
         JsonValue::Array(ref value) => map_valid_targets(value),
-        _ => Vec::new()
+        _ => Ok(Vec::new())
     }
 }
 
-fn map_valid_targets(value: &[JsonValue]) -> Vec<String> {
+/// Targets are either a path, or an object with the path, the options for the directories created
+/// within the target and the conflict policy.
+fn map_valid_targets(value: &[JsonValue]) -> Result<Vec<Target>, ConfigurationError> {
     value.iter()
-        .map(|v| v.to_string())
+        .enumerate()
+        .filter_map(|(index, v)| {
+            if !v.is_object() {
+                return Some(Ok(Target::new(v.to_string())));
+            }
+
+            v["path"].as_str()
+                .and_then(map_valid_source)
+                .map(|path| {
+                    map_target(path, v)
+                        .map_err(|e| ConfigurationError::InvalidTarget(index, e))
+                })
+        })
         .collect()
 }
 
-fn map_excludes(data: &JsonValue) -> Vec<String> {
+fn map_target(path: String, value: &JsonValue) -> Result<Target, String> {
+    let on_conflict = value["onConflict"].as_str()
+        .map(ConflictPolicy::from_str)
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(
+        Target::new(path)
+            .with_directory(map_directory_options(value)?)
+            .with_on_conflict(on_conflict)
+            .with_max_depth(value["maxDepth"].as_usize())
+    )
+}
+
+fn map_directory_options(value: &JsonValue) -> Result<DirectoryOptions, String> {
+    Ok(
+        DirectoryOptions {
+            mode: value["directoryMode"].as_str().map(parse_mode).transpose()?,
+            owner: value["owner"].as_str().map(resolve_user).transpose()?,
+            group: value["group"].as_str().map(resolve_group).transpose()?,
+        }
+    )
+}
+
+/// The name of a link is joined to the target, so a name containing `/`, `.` or `..` would create
+/// the link outside of the target or replace the target itself.
+pub(crate) fn validate_link_name(name: &[u8]) -> Result<(), String> {
+    if name.contains(&b'/') {
+        return Err(format!("Invalid link name \"{}\", must not contain /", String::from_utf8_lossy(name)));
+    }
+    if name == b"." || name == b".." {
+        return Err(format!("Invalid link name \"{}\"", String::from_utf8_lossy(name)));
+    }
+
+    Ok(())
+}
+
+/// Parses the mode as an octal number, e.g. `0750`.
+pub(crate) fn parse_mode(value: &str) -> Result<u32, String> {
+    u32::from_str_radix(value, 8)
+        .ok()
+        .filter(|v| *v <= 0o7777)
+        .ok_or_else(|| format!("Invalid directory mode {:?}, expected an octal mode, e.g. 0755", value))
+}
+
+/// Resolves the user by either name or id.
+pub(crate) fn resolve_user(value: &str) -> Result<u32, String> {
+    if let Ok(uid) = value.parse::<u32>() {
+        return Ok(uid);
+    }
+
+    match User::from_name(value) {
+        Ok(Some(user)) => Ok(user.uid.as_raw()),
+        _ => Err(format!("Unknown user {:?}", value)),
+    }
+}
+
+/// Resolves the group by either name or id.
+pub(crate) fn resolve_group(value: &str) -> Result<u32, String> {
+    if let Ok(gid) = value.parse::<u32>() {
+        return Ok(gid);
+    }
+
+    match Group::from_name(value) {
+        Ok(Some(group)) => Ok(group.gid.as_raw()),
+        _ => Err(format!("Unknown group {:?}", value)),
+    }
+}
+
+fn map_excludes(data: &JsonValue) -> Result<Vec<Exclude>, ConfigurationError> {
     match data["excludes"] {
         JsonValue::Array(ref value) => map_valid_excludes(value),
-        _ => Vec::new()
+        _ => Ok(Vec::new())
     }
 }
 
-fn map_valid_excludes(value: &[JsonValue]) -> Vec<String> {
+fn map_valid_excludes(value: &[JsonValue]) -> Result<Vec<Exclude>, ConfigurationError> {
     value.iter()
-        .map(|v| v.to_string())
-        .map(|v| v.to_lowercase())
+        .enumerate()
+        .filter_map(|(index, v)| {
+            map_valid_exclude(v)
+                .map_err(|e| ConfigurationError::InvalidExclude(index, e))
+                .transpose()
+        })
         .collect()
 }
 
-fn map_link_maps(data: &JsonValue) -> Vec<LinkMap> {
+/// Excludes are either configured as a glob pattern, or as an object with a `glob` or `regex`
+/// pattern and an optional `type` and `scope`. An object without exactly one of the patterns is an
+/// error rather than being ignored, since whatever it was meant to exclude would be linked.
+fn map_valid_exclude(value: &JsonValue) -> Result<Option<Exclude>, String> {
+    if value.is_string() {
+        let pattern = value.to_string();
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+        return Exclude::new(pattern)
+            .map(Some)
+            .map_err(|e| e.to_string());
+    }
+    if !value.is_object() {
+        return Err("must be a string or an object".to_string());
+    }
+
+    let node_type = value["type"].as_str()
+        .map(ExcludeType::from_str)
+        .transpose()?;
+    let scope = value["scope"].as_str()
+        .map(ExcludeScope::from_str)
+        .transpose()?;
+
+    match (value.has_key("glob"), value.has_key("regex")) {
+        (true, true) => Err("must not have both glob and regex".to_string()),
+        (false, false) => Err("must have either glob or regex".to_string()),
+        (true, false) => match value["glob"].as_str() {
+            Some("") => Ok(None),
+            Some(pattern) => Exclude::from_glob(pattern.to_string(), node_type, scope)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Err("glob must be a string".to_string()),
+        },
+        (false, true) => match value["regex"].as_str() {
+            Some("") => Ok(None),
+            Some(pattern) => Exclude::from_regex(pattern.to_string(), node_type, scope)
+                .map(Some)
+                .map_err(|e| e.to_string()),
+            None => Err("regex must be a string".to_string()),
+        },
+    }
+}
+
+fn map_link_maps(data: &JsonValue) -> Result<Vec<LinkMap>, ConfigurationError> {
     match data["linkMaps"] {
         JsonValue::Array(ref value) => map_valid_link_maps(value),
-        _ => Vec::new(),
+        _ => Ok(Vec::new()),
     }
 }
 
-fn map_valid_link_maps(value: &[JsonValue]) -> Vec<LinkMap> {
+fn map_valid_link_maps(value: &[JsonValue]) -> Result<Vec<LinkMap>, ConfigurationError> {
     value.iter()
-        .filter(|v| v["regex"].is_string() && v["target"].is_string())
-        .map(|v| {
+        .enumerate()
+        .filter(|(_, v)| v["regex"].is_string() && v["target"].is_string())
+        .map(|(index, v)| {
             let regex = v["regex"].to_string();
             let target = v["target"].to_string();
-            (regex, target)
+            (index, regex, target, v)
         })
-        .filter(|(regex, target)| !regex.is_empty() && !target.is_empty())
-        .map(|(regex, target)| {
-            LinkMap::new(regex, target)
-                .expect("Unable to create link map")
+        .filter(|(_, regex, target, _)| !regex.is_empty() && !target.is_empty())
+        .map(|(index, regex, target, v)| {
+            let link_map = LinkMap::new(regex.to_string(), target)
+                .map_err(|e| ConfigurationError::InvalidRegex(index, regex, e))?;
+            let link_map = match v["name"].as_str() {
+                Some(name) if !name.is_empty() => {
+                    validate_link_name(name.as_bytes())
+                        .map_err(|e| ConfigurationError::InvalidLinkMap(index, e))?;
+                    link_map.with_name(name.to_string())
+                }
+                _ => link_map,
+            };
+            let link_map = match v["relative"].as_bool() {
+                Some(relative) => link_map.with_relative(relative),
+                None => link_map,
+            };
+            match v["mode"].as_str() {
+                Some(mode) => LinkMode::parse_modes(mode)
+                    .map(|modes| link_map.with_modes(modes))
+                    .map_err(|e| ConfigurationError::InvalidLinkMap(index, e)),
+                None => Ok(link_map),
+            }
         })
         .collect()
 }
 
-//noinspection DuplicatedCode
-#[cfg(test)]
-mod tests {
-    use super::*;
+fn map_journal_options(data: &JsonValue) -> JournalOptions {
+    JournalOptions {
+        transactional: data["transactional"].as_bool().unwrap_or_default(),
+        directory: data["journal"].as_str()
+            .map(|v| v.to_string()),
+    }
+}
+
+fn map_link_options(data: &JsonValue) -> LinkOptions {
+    LinkOptions {
+        relative: data["relative"].as_bool().unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+fn map_match_strategy(data: &JsonValue) -> Result<MatchStrategy, ConfigurationError> {
+    match data["matchStrategy"].as_str() {
+        Some(value) => MatchStrategy::from_str(value).map_err(ConfigurationError::InvalidMatchStrategy),
+        None => Ok(MatchStrategy::default()),
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn create_file_with_content(path: &Path, content: &str) -> String {
+        fs::write(path, content)
+            .unwrap_or_else(|_| panic!("Unable to create file at: {:?}", path));
+
+        path.to_str()
+            .map(|v| v.to_string())
+            .expect("Unable to build path for file")
+    }
+
+    fn parse_configuration(configuration: &str, format: ConfigurationFormat) -> Configuration {
+        if configuration.is_empty() {
+            return Default::default();
+        }
+
+        let data = parse_document(format, configuration).unwrap();
+        map_configuration(&data).unwrap()
+    }
+
+    #[test]
+    fn parse_configuration_with_empty_configuration() {
+        let configuration: &str = "";
+        let expected: Configuration = Default::default();
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_without_source() {
+        let configuration: &str = r#"
+        {
+        }
+        "#;
+        let expected: Configuration = Default::default();
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_empty_source() {
+        let configuration: &str = r#"
+        {
+            "source": ""
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: Vec::new(),
+            targets: Vec::new(),
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_sources() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "sources": [
+                "/var/cache/aur/pkg",
+                {
+                    "path": "/var/cache/build/pkg",
+                    "priority": 10
+                },
+                {
+                    "path": "/var/cache/mirror/pkg",
+                    "priority": -10
+                },
+                ""
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/build/pkg".to_string(), 10),
+                Source::new("/var/cache/pacman/pkg".to_string(), 0),
+                Source::new("/var/cache/aur/pkg".to_string(), 0),
+                Source::new("/var/cache/mirror/pkg".to_string(), -10),
+            ],
+            targets: Vec::new(),
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_without_targets() {
+        let configuration: &str = r#"
+        {
+            "source": "/tmp"
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/tmp".to_string(), 0)
+            ],
+            targets: Vec::new(),
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_empty_targets() {
+        let configuration: &str = r#"
+        {
+            "source": "/tmp",
+            "targets": []
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/tmp".to_string(), 0)
+            ],
+            targets: Vec::new(),
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_required_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_without_link_map_regex_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*zip"
+            ],
+            "linkMaps": [
+                {
+                    "target": "/var/www/archlinux/pkg"
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_without_link_map_target_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*zip"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.xz"
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_empty_link_map_regex_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*zip"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "",
+                    "target": "/var/www/archlinux/pkg"
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_empty_link_map_target_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*zip"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.xz",
+                    "target": ""
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_full_configuration() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*zip"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.xz",
+                    "target": "/var/www/archlinux/pkg"
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap()
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_toml_configuration() {
+        let configuration: &str = r#"
+        source = "/var/cache/pacman/pkg"
+        targets = ["/var/www/archlinux/pkg"]
+        excludes = ["*zip"]
+
+        # Link every package to the web repository.
+        [[linkMaps]]
+        regex = '(.*)\.pkg\.tar\.xz'
+        target = "/var/www/archlinux/pkg"
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: vec![
+                Exclude::new("*zip".to_string()).unwrap()
+            ],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap()
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Toml);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_link_map_name() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)-x86_64\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg",
+                    "name": "$1.pkg.tar.zst"
+                },
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.xz",
+                    "target": "/var/www/archlinux/pkg",
+                    "name": ""
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: Vec::new(),
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)-x86_64\\.pkg\\.tar\\.zst".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap().with_name("$1.pkg.tar.zst".to_string()),
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_match_strategy() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "matchStrategy": "all"
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: Vec::new(),
+            link_maps: Vec::new(),
+            match_strategy: MatchStrategy::All,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_relative_links() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "relative": true,
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg"
+                },
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.xz",
+                    "target": "/var/www/archlinux/pkg",
+                    "relative": false
+                }
+            ]
+        }
+        "#;
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string())
+            ],
+            excludes: Vec::new(),
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap(),
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "/var/www/archlinux/pkg".to_string(),
+                ).unwrap().with_relative(false),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions {
+                relative: true,
+                ..Default::default()
+            },
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_link_map_mode() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg",
+                    "mode": "reflink, hardlink,copy"
+                }
+            ]
+        }
+        "#;
+        let expected: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                "/var/www/archlinux/pkg".to_string(),
+            ).unwrap().with_modes(vec![LinkMode::Reflink, LinkMode::Hardlink, LinkMode::Copy]),
+        ];
+
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual.link_maps)
+    }
+
+    #[test]
+    fn map_configuration_with_invalid_link_map_mode() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg",
+                    "mode": "reflink,move"
+                }
+            ]
+        }
+        "#;
+        let expected = Err(
+            ConfigurationError::InvalidLinkMap(
+                0,
+                "Invalid link mode \"move\", expected symlink, hardlink, reflink or copy".to_string(),
+            )
+        );
+
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_invalid_link_map_name() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg",
+                    "name": "../$1.pkg.tar.zst"
+                }
+            ]
+        }
+        "#;
+        let expected = Err(
+            ConfigurationError::InvalidLinkMap(
+                0,
+                "Invalid link name \"../$1.pkg.tar.zst\", must not contain /".to_string(),
+            )
+        );
+
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_configuration_with_target_directory_options() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg",
+                {
+                    "path": "/var/www/archlinux/latest",
+                    "directoryMode": "0750",
+                    "owner": "0",
+                    "group": "0"
+                }
+            ]
+        }
+        "#;
+        let expected: Vec<Target> = vec![
+            Target::new("/var/www/archlinux/pkg".to_string()),
+            Target::new("/var/www/archlinux/latest".to_string())
+                .with_directory(DirectoryOptions {
+                    mode: Some(0o750),
+                    owner: Some(0),
+                    group: Some(0),
+                }),
+        ];
+
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
+
+        assert_eq!(expected, actual.targets)
+    }
 
     #[test]
-    fn parse_configuration_with_empty_configuration() {
-        let configuration: &str = "";
-        let expected: Configuration = Default::default();
+    fn parse_configuration_with_max_depth() {
+        let configuration: &str = r#"
+        {
+            "sources": [
+                { "path": "/var/cache/pacman/pkg", "maxDepth": 2 }
+            ],
+            "targets": [
+                "/var/www/archlinux/pkg",
+                { "path": "/var/www/archlinux/latest", "maxDepth": 0 }
+            ]
+        }
+        "#;
+        let expected = (
+            vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+                    .with_max_depth(Some(2)),
+            ],
+            vec![
+                Target::new("/var/www/archlinux/pkg".to_string()),
+                Target::new("/var/www/archlinux/latest".to_string())
+                    .with_max_depth(Some(0)),
+            ],
+        );
 
-        let actual = parse_configuration(&configuration);
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
 
-        assert_eq!(expected, actual)
+        assert_eq!(expected, (actual.sources, actual.targets))
     }
 
     #[test]
-    fn parse_configuration_without_source() {
+    fn parse_configuration_with_follow_source_links() {
         let configuration: &str = r#"
         {
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "followSourceLinks": true
         }
         "#;
-        let expected: Configuration = Default::default();
 
-        let actual = parse_configuration(&configuration);
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
 
-        assert_eq!(expected, actual)
+        assert!(actual.follow_source_links)
     }
 
     #[test]
-    fn parse_configuration_with_empty_source() {
+    fn parse_configuration_with_transactional_journal() {
         let configuration: &str = r#"
         {
-            "source": ""
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "transactional": true,
+            "journal": "/var/lib/linker"
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: None,
-            targets: Vec::new(),
-            excludes: Vec::new(),
-            link_maps: Vec::new(),
+        let expected = JournalOptions {
+            transactional: true,
+            directory: Some("/var/lib/linker".to_string()),
         };
 
-        let actual = parse_configuration(&configuration);
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
 
-        assert_eq!(expected, actual)
+        assert_eq!(expected, actual.journal)
     }
 
     #[test]
-    fn parse_configuration_without_targets() {
+    fn parse_configuration_with_target_conflict_policy() {
         let configuration: &str = r#"
         {
-            "source": "/tmp"
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg",
+                { "path": "/var/www/archlinux/latest", "onConflict": "backup" }
+            ]
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: Some("/tmp".to_string()),
-            targets: Vec::new(),
-            excludes: Vec::new(),
-            link_maps: Vec::new(),
-        };
+        let expected: Vec<Target> = vec![
+            Target::new("/var/www/archlinux/pkg".to_string()),
+            Target::new("/var/www/archlinux/latest".to_string())
+                .with_on_conflict(ConflictPolicy::Backup),
+        ];
 
-        let actual = parse_configuration(&configuration);
+        let actual = parse_configuration(configuration, ConfigurationFormat::Json);
 
-        assert_eq!(expected, actual)
+        assert_eq!(expected, actual.targets)
     }
 
     #[test]
-    fn parse_configuration_with_empty_targets() {
+    fn map_configuration_with_invalid_target_conflict_policy() {
         let configuration: &str = r#"
         {
-            "source": "/tmp",
-            "targets": []
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                { "path": "/var/www/archlinux/pkg", "onConflict": "overwrite" }
+            ]
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: Some("/tmp".to_string()),
-            targets: Vec::new(),
-            excludes: Vec::new(),
-            link_maps: Vec::new(),
-        };
+        let expected = Err(
+            ConfigurationError::InvalidTarget(
+                0,
+                "Invalid conflict policy \"overwrite\", expected skip, fail, replace, backup or suffix".to_string(),
+            )
+        );
 
-        let actual = parse_configuration(&configuration);
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_with_required_configuration() {
+    fn map_configuration_with_invalid_target_directory_mode() {
         let configuration: &str = r#"
         {
             "source": "/var/cache/pacman/pkg",
             "targets": [
-                "/var/www/archlinux/pkg"
+                { "path": "/var/www/archlinux/pkg", "directoryMode": "0980" }
             ]
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
-            targets: vec![
-                "/var/www/archlinux/pkg".to_string()
-            ],
-            excludes: Vec::new(),
-            link_maps: Vec::new(),
+        let expected = Err(
+            ConfigurationError::InvalidTarget(
+                0,
+                "Invalid directory mode \"0980\", expected an octal mode, e.g. 0755".to_string(),
+            )
+        );
+
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn link_options_with_link_map_override() {
+        let defaults = LinkOptions {
+            relative: true,
+            ..Default::default()
         };
+        let link_map = LinkMap::new(
+            "leaf".to_string(),
+            "/var/tmp/targets".to_string(),
+        ).unwrap();
+
+        assert_eq!(defaults, link_map.link_options(&defaults));
+        assert_eq!(LinkOptions::default(), link_map.with_relative(false).link_options(&defaults))
+    }
+
+    #[test]
+    fn map_configuration_with_invalid_match_strategy() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "matchStrategy": "random"
+        }
+        "#;
+        let expected = Err(
+            ConfigurationError::InvalidMatchStrategy(
+                "Invalid match strategy \"random\", expected first, last, all or error".to_string()
+            )
+        );
 
-        let actual = parse_configuration(&configuration);
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_without_link_map_regex_configuration() {
+    fn parse_configuration_with_typed_excludes() {
         let configuration: &str = r#"
         {
             "source": "/var/cache/pacman/pkg",
@@ -270,33 +1788,56 @@ mod tests {
                 "/var/www/archlinux/pkg"
             ],
             "excludes": [
-                "*zip"
-            ],
-            "linkMaps": [
+                "*.part",
                 {
-                    "target": "/var/www/archlinux/pkg"
+                    "glob": "build",
+                    "type": "branch"
+                },
+                {
+                    "regex": "^(?i)cache/.*\\.sig$",
+                    "type": "leaf",
+                    "scope": "relative"
+                },
+                {
+                    "regex": ""
                 }
             ]
         }
         "#;
         let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0)
+            ],
             targets: vec![
-                "/var/www/archlinux/pkg".to_string()
+                Target::new("/var/www/archlinux/pkg".to_string())
             ],
             excludes: vec![
-                "*zip".to_string()
+                Exclude::new("*.part".to_string()).unwrap(),
+                Exclude::from_glob(
+                    "build".to_string(),
+                    Some(ExcludeType::Branch),
+                    None,
+                ).unwrap(),
+                Exclude::from_regex(
+                    "^(?i)cache/.*\\.sig$".to_string(),
+                    Some(ExcludeType::Leaf),
+                    Some(ExcludeScope::Relative),
+                ).unwrap(),
             ],
             link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
 
-        let actual = parse_configuration(&configuration);
+        let actual = parse_configuration(&configuration, ConfigurationFormat::Json);
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_without_link_map_target_configuration() {
+    fn map_configuration_with_invalid_exclude_type() {
         let configuration: &str = r#"
         {
             "source": "/var/cache/pacman/pkg",
@@ -304,33 +1845,28 @@ mod tests {
                 "/var/www/archlinux/pkg"
             ],
             "excludes": [
-                "*zip"
-            ],
-            "linkMaps": [
+                "*.part",
                 {
-                    "regex": "(.*)\\.pkg\\.tar\\.xz"
+                    "glob": "build",
+                    "type": "directory"
                 }
             ]
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
-            targets: vec![
-                "/var/www/archlinux/pkg".to_string()
-            ],
-            excludes: vec![
-                "*zip".to_string()
-            ],
-            link_maps: Vec::new(),
-        };
+        let expected = Err(
+            ConfigurationError::InvalidExclude(
+                1,
+                "Invalid exclude type \"directory\", expected leaf, branch or link".to_string(),
+            )
+        );
 
-        let actual = parse_configuration(&configuration);
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_with_empty_link_map_regex_configuration() {
+    fn map_configuration_with_exclude_without_pattern() {
         let configuration: &str = r#"
         {
             "source": "/var/cache/pacman/pkg",
@@ -338,34 +1874,23 @@ mod tests {
                 "/var/www/archlinux/pkg"
             ],
             "excludes": [
-                "*zip"
-            ],
-            "linkMaps": [
                 {
-                    "regex": "",
-                    "target": "/var/www/archlinux/pkg"
+                    "regx": ".*\\.part"
                 }
             ]
         }
         "#;
-        let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
-            targets: vec![
-                "/var/www/archlinux/pkg".to_string()
-            ],
-            excludes: vec![
-                "*zip".to_string()
-            ],
-            link_maps: Vec::new(),
-        };
+        let expected = Err(
+            ConfigurationError::InvalidExclude(0, "must have either glob or regex".to_string())
+        );
 
-        let actual = parse_configuration(&configuration);
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_with_empty_link_map_target_configuration() {
+    fn map_configuration_with_exclude_with_both_patterns() {
         let configuration: &str = r#"
         {
             "source": "/var/cache/pacman/pkg",
@@ -373,68 +1898,362 @@ mod tests {
                 "/var/www/archlinux/pkg"
             ],
             "excludes": [
-                "*zip"
+                {
+                    "glob": "*.part",
+                    "regex": ".*\\.part"
+                }
+            ]
+        }
+        "#;
+        let expected = Err(
+            ConfigurationError::InvalidExclude(0, "must not have both glob and regex".to_string())
+        );
+
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn map_configuration_with_invalid_link_map_regex() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
             ],
             "linkMaps": [
                 {
                     "regex": "(.*)\\.pkg\\.tar\\.xz",
-                    "target": ""
+                    "target": "/var/www/archlinux/pkg"
+                },
+                {
+                    "regex": "(.*\\.pkg\\.tar\\.xz",
+                    "target": "/var/www/archlinux/pkg"
                 }
             ]
         }
         "#;
+
+        let actual = map_configuration(&parse_document(ConfigurationFormat::Json, configuration).unwrap());
+
+        assert!(matches!(actual, Err(ConfigurationError::InvalidRegex(1, pattern, _)) if pattern == "(.*\\.pkg\\.tar\\.xz"))
+    }
+
+    #[test]
+    fn read_configuration_without_file() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path()).join("configuration.json");
+        let path = path.to_str().unwrap();
+        let expected = Err(
+            ConfigurationError::Io(
+                path.to_string(),
+                std::io::Error::from(std::io::ErrorKind::NotFound),
+            )
+        );
+
+        let actual = read_configuration(path);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_configuration_without_source() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
+        {
+            "targets": ["/var/www/archlinux/pkg"]
+        }
+        "#);
+        let expected = Err(ConfigurationError::MissingSource);
+
+        let actual = read_configuration(&configuration);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_configuration_without_targets() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
+        {
+            "source": "/var/cache/pacman/pkg"
+        }
+        "#);
+        let expected = Err(ConfigurationError::MissingTargets);
+
+        let actual = read_configuration(&configuration);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn expand_configuration_paths_with_relative_paths() {
+        let configuration: Configuration = Configuration {
+            sources: vec![
+                Source::new("pkg".to_string(), 10),
+                Source::new("/var/cache/pacman/pkg".to_string(), 0),
+            ],
+            targets: vec![
+                Target::new("../www/pkg".to_string())
+            ],
+            excludes: Vec::new(),
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "${LINKER_UNDEFINED_VARIABLE:-www}/pkg".to_string(),
+                ).unwrap()
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
         let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
+            sources: vec![
+                Source::new("/etc/linker/pkg".to_string(), 10),
+                Source::new("/var/cache/pacman/pkg".to_string(), 0),
+            ],
             targets: vec![
-                "/var/www/archlinux/pkg".to_string()
+                Target::new("/etc/www/pkg".to_string())
             ],
-            excludes: vec![
-                "*zip".to_string()
+            excludes: Vec::new(),
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "/etc/linker/www/pkg".to_string(),
+                ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = expand_configuration_paths(configuration, Path::new("/etc/linker")).unwrap();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn expand_configuration_paths_with_undefined_variable() {
+        let configuration: Configuration = Configuration {
+            sources: vec![
+                Source::new("$LINKER_UNDEFINED_VARIABLE/pkg".to_string(), 0),
+            ],
+            targets: Vec::new(),
+            excludes: Vec::new(),
             link_maps: Vec::new(),
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
 
-        let actual = parse_configuration(&configuration);
+        let expected = Err(
+            ConfigurationError::UndefinedVariable(
+                "LINKER_UNDEFINED_VARIABLE".to_string(),
+                "$LINKER_UNDEFINED_VARIABLE/pkg".to_string(),
+            )
+        );
+
+        let actual = expand_configuration_paths(configuration, Path::new("/etc/linker"));
 
         assert_eq!(expected, actual)
     }
 
     #[test]
-    fn parse_configuration_with_full_configuration() {
-        let configuration: &str = r#"
+    fn read_configuration_with_includes() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        fs::create_dir(path.join("conf.d"))
+            .expect("Unable to create directory");
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
         {
             "source": "/var/cache/pacman/pkg",
-            "targets": [
-                "/var/www/archlinux/pkg"
-            ],
-            "excludes": [
-                "*zip"
-            ],
+            "targets": ["/var/www/archlinux/pkg"],
+            "include": ["conf.d/*.json", "team.toml"],
             "linkMaps": [
                 {
-                    "regex": "(.*)\\.pkg\\.tar\\.xz",
+                    "regex": "leaf",
                     "target": "/var/www/archlinux/pkg"
                 }
             ]
         }
-        "#;
+        "#);
+        create_file_with_content(&path.join("conf.d").join("20-excludes.json"), r#"
+        {
+            "excludes": ["*.part"]
+        }
+        "#);
+        create_file_with_content(&path.join("conf.d").join("10-link-maps.json"), r#"
+        {
+            "targets": ["/var/www/archlinux/pkg", "/var/www/archlinux/aur"],
+            "linkMaps": [
+                {
+                    "regex": "aur-leaf",
+                    "target": "/var/www/archlinux/aur"
+                }
+            ]
+        }
+        "#);
+        create_file_with_content(&path.join("team.toml"), r#"
+        [[linkMaps]]
+        regex = "team-leaf"
+        target = "team"
+        "#);
         let expected: Configuration = Configuration {
-            source: Some("/var/cache/pacman/pkg".to_string()),
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0),
+            ],
             targets: vec![
-                "/var/www/archlinux/pkg".to_string()
+                Target::new("/var/www/archlinux/pkg".to_string()),
+                Target::new("/var/www/archlinux/aur".to_string()),
             ],
             excludes: vec![
-                "*zip".to_string()
+                Exclude::new("*.part".to_string()).unwrap()
             ],
             link_maps: vec![
                 LinkMap::new(
-                    "(.*)\\.pkg\\.tar\\.xz".to_string(),
+                    "leaf".to_string(),
                     "/var/www/archlinux/pkg".to_string(),
-                ).unwrap()
+                ).unwrap(),
+                LinkMap::new(
+                    "aur-leaf".to_string(),
+                    "/var/www/archlinux/aur".to_string(),
+                ).unwrap(),
+                LinkMap::new(
+                    "team-leaf".to_string(),
+                    fs::canonicalize(&path).unwrap().join("team").to_str().unwrap().to_string(),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+
+        let actual = read_configuration(&configuration).unwrap();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn resolve_include_without_matching_files() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let expected: Vec<String> = Vec::new();
+
+        let actual = resolve_include("conf.d/*.json", &path).unwrap();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_configuration_with_missing_include() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "include": ["missing.json"]
+        }
+        "#);
+        let missing = fs::canonicalize(&path).unwrap().join("missing.json").to_str().unwrap().to_string();
+        let expected = Err(
+            ConfigurationError::Include(
+                missing.to_string(),
+                Box::new(
+                    ConfigurationError::Io(
+                        missing,
+                        std::io::Error::from(std::io::ErrorKind::NotFound),
+                    )
+                ),
+            )
+        );
+
+        let actual = read_configuration(&configuration);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_configuration_with_nested_includes() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "include": ["team.json"]
+        }
+        "#);
+        create_file_with_content(&path.join("team.json"), r#"
+        {
+            "include": ["targets.json"],
+            "excludes": ["*.part"]
+        }
+        "#);
+        create_file_with_content(&path.join("targets.json"), r#"
+        {
+            "targets": ["/var/www/archlinux/pkg"]
+        }
+        "#);
+        let expected: Configuration = Configuration {
+            sources: vec![
+                Source::new("/var/cache/pacman/pkg".to_string(), 0),
+            ],
+            targets: vec![
+                Target::new("/var/www/archlinux/pkg".to_string()),
+            ],
+            excludes: vec![
+                Exclude::new("*.part".to_string()).unwrap()
             ],
+            link_maps: vec![],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
 
-        let actual = parse_configuration(&configuration);
+        let actual = read_configuration(&configuration).unwrap();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_configuration_with_include_cycle() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let configuration = create_file_with_content(&path.join("configuration.json"), r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "include": ["team.json"]
+        }
+        "#);
+        create_file_with_content(&path.join("team.json"), r#"
+        {
+            "include": ["configuration.json"]
+        }
+        "#);
+        let team = fs::canonicalize(&path).unwrap().join("team.json").to_str().unwrap().to_string();
+        let included = fs::canonicalize(&path).unwrap().join("configuration.json").to_str().unwrap().to_string();
+        let expected = Err(
+            ConfigurationError::Include(
+                team,
+                Box::new(
+                    ConfigurationError::Include(
+                        included.to_string(),
+                        Box::new(ConfigurationError::IncludeCycle(included)),
+                    )
+                ),
+            )
+        );
+
+        let actual = read_configuration(&configuration);
 
         assert_eq!(expected, actual)
     }
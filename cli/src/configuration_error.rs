@@ -0,0 +1,140 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub(crate) enum ConfigurationError {
+    Io(String, std::io::Error),
+    Parse(Option<(usize, usize)>, String),
+    InvalidRegex(usize, String, regex::Error),
+    InvalidExclude(usize, String),
+    InvalidLinkMap(usize, String),
+    InvalidTarget(usize, String),
+    InvalidInclude(String, String),
+    InvalidMatchStrategy(String),
+    UndefinedVariable(String, String),
+    InvalidPath(String, String),
+    Include(String, Box<ConfigurationError>),
+    IncludeCycle(String),
+    MissingSource,
+    MissingTargets,
+}
+
+impl Eq for ConfigurationError {}
+
+impl PartialEq<Self> for ConfigurationError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ConfigurationError::Io(lhs_path, lhs), ConfigurationError::Io(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs.kind() == rhs.kind()
+            }
+            (ConfigurationError::Parse(lhs_position, lhs), ConfigurationError::Parse(rhs_position, rhs)) => {
+                lhs_position == rhs_position && lhs == rhs
+            }
+            (
+                ConfigurationError::InvalidRegex(lhs_index, lhs_pattern, lhs),
+                ConfigurationError::InvalidRegex(rhs_index, rhs_pattern, rhs)
+            ) => {
+                lhs_index == rhs_index && lhs_pattern == rhs_pattern && lhs == rhs
+            }
+            (ConfigurationError::InvalidExclude(lhs_index, lhs), ConfigurationError::InvalidExclude(rhs_index, rhs)) => {
+                lhs_index == rhs_index && lhs == rhs
+            }
+            (ConfigurationError::InvalidLinkMap(lhs_index, lhs), ConfigurationError::InvalidLinkMap(rhs_index, rhs)) => {
+                lhs_index == rhs_index && lhs == rhs
+            }
+            (ConfigurationError::InvalidTarget(lhs_index, lhs), ConfigurationError::InvalidTarget(rhs_index, rhs)) => {
+                lhs_index == rhs_index && lhs == rhs
+            }
+            (ConfigurationError::InvalidInclude(lhs_include, lhs), ConfigurationError::InvalidInclude(rhs_include, rhs)) => {
+                lhs_include == rhs_include && lhs == rhs
+            }
+            (ConfigurationError::InvalidMatchStrategy(lhs), ConfigurationError::InvalidMatchStrategy(rhs)) => {
+                lhs == rhs
+            }
+            (ConfigurationError::UndefinedVariable(lhs_name, lhs), ConfigurationError::UndefinedVariable(rhs_name, rhs)) => {
+                lhs_name == rhs_name && lhs == rhs
+            }
+            (ConfigurationError::InvalidPath(lhs_path, lhs), ConfigurationError::InvalidPath(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs == rhs
+            }
+            (ConfigurationError::Include(lhs_path, lhs), ConfigurationError::Include(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs == rhs
+            }
+            (ConfigurationError::IncludeCycle(lhs), ConfigurationError::IncludeCycle(rhs)) => lhs == rhs,
+            (ConfigurationError::MissingSource, ConfigurationError::MissingSource) => true,
+            (ConfigurationError::MissingTargets, ConfigurationError::MissingTargets) => true,
+            _ => false
+        }
+    }
+}
+
+impl Error for ConfigurationError {}
+
+impl Display for ConfigurationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigurationError::Io(path, e) => {
+                write!(f, "Unable to read configuration file at path {}: {}", path, e)
+            }
+            ConfigurationError::Parse(Some((line, column)), message) => {
+                write!(f, "Unable to parse configuration at line {}, column {}: {}", line, column, message)
+            }
+            ConfigurationError::Parse(None, message) => {
+                write!(f, "Unable to parse configuration: {}", message)
+            }
+            ConfigurationError::InvalidRegex(index, pattern, e) => {
+                write!(f, "Invalid regex {:?} for link map at index {}: {}", pattern, index, e)
+            }
+            ConfigurationError::InvalidExclude(index, message) => {
+                write!(f, "Invalid exclude at index {}: {}", index, message)
+            }
+            ConfigurationError::InvalidLinkMap(index, message) => {
+                write!(f, "Invalid link map at index {}: {}", index, message)
+            }
+            ConfigurationError::InvalidTarget(index, message) => {
+                write!(f, "Invalid target at index {}: {}", index, message)
+            }
+            ConfigurationError::InvalidInclude(include, message) => {
+                write!(f, "Invalid include {:?}: {}", include, message)
+            }
+            ConfigurationError::InvalidMatchStrategy(message) => {
+                write!(f, "{}", message)
+            }
+            ConfigurationError::UndefinedVariable(name, path) => {
+                write!(f, "Environment variable {} is not defined, referenced in {:?}", name, path)
+            }
+            ConfigurationError::InvalidPath(path, message) => {
+                write!(f, "Invalid path {:?}: {}", path, message)
+            }
+            ConfigurationError::Include(path, e) => {
+                write!(f, "Included configuration file {}: {}", path, e)
+            }
+            ConfigurationError::IncludeCycle(path) => {
+                write!(f, "Configuration file {} includes itself", path)
+            }
+            ConfigurationError::MissingSource => {
+                write!(f, "Configuration is missing valid source")
+            }
+            ConfigurationError::MissingTargets => {
+                write!(f, "Configuration is missing valid targets")
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::path::Path;
+
+use json::JsonValue;
+
+use crate::configuration_error::ConfigurationError;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ConfigurationFormat {
+    Json,
+    Json5,
+    Toml,
+    Yaml,
+}
+
+impl ConfigurationFormat {
+    /// Detects the format from the extension of the configuration file, files without a known
+    /// extension are treated as JSON.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|v| v.to_str())
+            .map(|v| v.to_lowercase());
+
+        match extension.as_deref() {
+            Some("jsonc") | Some("json5") => ConfigurationFormat::Json5,
+            Some("toml") => ConfigurationFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigurationFormat::Yaml,
+            _ => ConfigurationFormat::Json,
+        }
+    }
+}
+
+/// Parses the configuration into a `JsonValue`, regardless of the format, so that every format
+/// is mapped and validated in the same way.
+pub fn parse_document(format: ConfigurationFormat, configuration: &str) -> Result<JsonValue, ConfigurationError> {
+    let value = match format {
+        ConfigurationFormat::Json => return json::parse(configuration).map_err(map_json_error),
+        ConfigurationFormat::Json5 => {
+            json5::from_str::<serde_json::Value>(configuration)
+                .map_err(map_json5_error)?
+        }
+        ConfigurationFormat::Toml => {
+            toml::from_str::<serde_json::Value>(configuration)
+                .map_err(|e| map_toml_error(configuration, e))?
+        }
+        ConfigurationFormat::Yaml => {
+            serde_yaml::from_str::<serde_json::Value>(configuration)
+                .map_err(map_yaml_error)?
+        }
+    };
+
+    Ok(map_value(value))
+}
+
+fn map_json_error(e: json::Error) -> ConfigurationError {
+    match e {
+        json::Error::UnexpectedCharacter { ch, line, column } => {
+            ConfigurationError::Parse(Some((line, column)), format!("Unexpected character: {}", ch))
+        }
+        _ => ConfigurationError::Parse(None, e.to_string()),
+    }
+}
+
+fn map_json5_error(e: json5::Error) -> ConfigurationError {
+    match e {
+        json5::Error::Message { msg, location } => {
+            ConfigurationError::Parse(location.map(|v| (v.line, v.column)), msg)
+        }
+    }
+}
+
+fn map_toml_error(configuration: &str, e: toml::de::Error) -> ConfigurationError {
+    let position = e.span()
+        .map(|span| {
+            let preceding = &configuration[..span.start];
+            let line = preceding.matches('\n').count() + 1;
+            let column = preceding.len() - preceding.rfind('\n').map_or(0, |v| v + 1) + 1;
+            (line, column)
+        });
+
+    ConfigurationError::Parse(position, e.message().to_string())
+}
+
+fn map_yaml_error(e: serde_yaml::Error) -> ConfigurationError {
+    let position = e.location()
+        .map(|v| (v.line(), v.column()));
+
+    ConfigurationError::Parse(position, e.to_string())
+}
+
+fn map_value(value: serde_json::Value) -> JsonValue {
+    match value {
+        serde_json::Value::Null => JsonValue::Null,
+        serde_json::Value::Bool(v) => JsonValue::Boolean(v),
+        serde_json::Value::Number(v) => {
+            v.as_i64()
+                .map(JsonValue::from)
+                .or_else(|| v.as_u64().map(JsonValue::from))
+                .unwrap_or_else(|| JsonValue::from(v.as_f64().unwrap_or_default()))
+        }
+        serde_json::Value::String(v) => JsonValue::String(v),
+        serde_json::Value::Array(values) => {
+            JsonValue::Array(values.into_iter().map(map_value).collect())
+        }
+        serde_json::Value::Object(values) => {
+            let mut object = JsonValue::new_object();
+            values.into_iter()
+                .for_each(|(key, value)| object[key] = map_value(value));
+            object
+        }
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expected_document() -> JsonValue {
+        json::object! {
+            "source": "/var/cache/pacman/pkg",
+            "targets": [
+                "/var/www/archlinux/pkg"
+            ],
+            "excludes": [
+                "*.part"
+            ],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg"
+                }
+            ]
+        }
+    }
+
+    #[test]
+    fn from_path_with_known_extensions() {
+        assert_eq!(ConfigurationFormat::Json, ConfigurationFormat::from_path("/etc/linker/configuration.json"));
+        assert_eq!(ConfigurationFormat::Json5, ConfigurationFormat::from_path("/etc/linker/configuration.jsonc"));
+        assert_eq!(ConfigurationFormat::Json5, ConfigurationFormat::from_path("/etc/linker/configuration.json5"));
+        assert_eq!(ConfigurationFormat::Toml, ConfigurationFormat::from_path("/etc/linker/configuration.toml"));
+        assert_eq!(ConfigurationFormat::Yaml, ConfigurationFormat::from_path("/etc/linker/configuration.yaml"));
+        assert_eq!(ConfigurationFormat::Yaml, ConfigurationFormat::from_path("/etc/linker/configuration.YML"));
+    }
+
+    #[test]
+    fn from_path_without_extension() {
+        assert_eq!(ConfigurationFormat::Json, ConfigurationFormat::from_path("/etc/linker/configuration"));
+    }
+
+    #[test]
+    fn parse_document_with_json() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "excludes": ["*.part"],
+            "linkMaps": [
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg"
+                }
+            ]
+        }
+        "#;
+
+        let actual = parse_document(ConfigurationFormat::Json, configuration).unwrap();
+
+        assert_eq!(expected_document(), actual)
+    }
+
+    #[test]
+    fn parse_document_with_json5() {
+        let configuration: &str = r#"
+        {
+            // Cache populated by pacman.
+            "source": "/var/cache/pacman/pkg",
+            "targets": ["/var/www/archlinux/pkg"],
+            "excludes": ["*.part"],
+            "linkMaps": [
+                /* Every package. */
+                {
+                    "regex": "(.*)\\.pkg\\.tar\\.zst",
+                    "target": "/var/www/archlinux/pkg",
+                },
+            ],
+        }
+        "#;
+
+        let actual = parse_document(ConfigurationFormat::Json5, configuration).unwrap();
+
+        assert_eq!(expected_document(), actual)
+    }
+
+    #[test]
+    fn parse_document_with_toml() {
+        let configuration: &str = r#"
+        # Cache populated by pacman.
+        source = "/var/cache/pacman/pkg"
+        targets = ["/var/www/archlinux/pkg"]
+        excludes = ["*.part"]
+
+        [[linkMaps]]
+        regex = '(.*)\.pkg\.tar\.zst'
+        target = "/var/www/archlinux/pkg"
+        "#;
+
+        let actual = parse_document(ConfigurationFormat::Toml, configuration).unwrap();
+
+        assert_eq!(expected_document(), actual)
+    }
+
+    #[test]
+    fn parse_document_with_yaml() {
+        let configuration: &str = r#"
+# Cache populated by pacman.
+source: /var/cache/pacman/pkg
+targets:
+  - /var/www/archlinux/pkg
+excludes:
+  - "*.part"
+linkMaps:
+  - regex: '(.*)\.pkg\.tar\.zst'
+    target: /var/www/archlinux/pkg
+"#;
+
+        let actual = parse_document(ConfigurationFormat::Yaml, configuration).unwrap();
+
+        assert_eq!(expected_document(), actual)
+    }
+
+    #[test]
+    fn parse_document_with_invalid_toml() {
+        let configuration: &str = r#"
+        source = "/var/cache/pacman/pkg
+        "#;
+
+        let expected = Err(
+            ConfigurationError::Parse(
+                Some((2, 40)),
+                "invalid basic string, expected `\"`".to_string(),
+            )
+        );
+
+        let actual = parse_document(ConfigurationFormat::Toml, configuration);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_document_with_invalid_json() {
+        let configuration: &str = r#"
+        {
+            "source": "/var/cache/pacman/pkg",
+        }
+        "#;
+        let expected = Err(ConfigurationError::Parse(Some((4, 9)), "Unexpected character: }".to_string()));
+
+        let actual = parse_document(ConfigurationFormat::Json, configuration);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn parse_document_with_invalid_yaml() {
+        let configuration: &str = "source: [/var/cache/pacman/pkg\n";
+
+        let actual = parse_document(ConfigurationFormat::Yaml, configuration);
+
+        assert!(matches!(actual, Err(ConfigurationError::Parse(Some((2, 1)), _))))
+    }
+}
@@ -0,0 +1,207 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use crate::configuration_error::ConfigurationError;
+
+/// Expands environment variables, i.e. `$VAR`, `${VAR}` and `${VAR:-default}`, and a leading `~`
+/// within the path. Relative paths are resolved against the directory of the configuration file,
+/// and any `.` and `..` are resolved lexically since the paths are compared with the canonical
+/// sources of the links within the targets.
+pub fn expand_path(path: &str, directory: &Path) -> Result<String, ConfigurationError> {
+    expand_path_with_lookup(path, directory, |v| env::var(v).ok())
+}
+
+fn expand_path_with_lookup<F>(path: &str, directory: &Path, lookup: F) -> Result<String, ConfigurationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let expanded = expand_home_directory(&expand_variables(path, &lookup)?, &lookup)?;
+    if expanded.is_empty() || Path::new(&expanded).is_absolute() {
+        return Ok(expanded);
+    }
+
+    normalize_path(&directory.join(&expanded))
+        .to_str()
+        .map(|v| v.to_string())
+        .ok_or_else(|| {
+            let message = format!("unable to resolve path against {:?}", directory);
+            ConfigurationError::InvalidPath(expanded.to_string(), message)
+        })
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn expand_home_directory<F>(path: &str, lookup: &F) -> Result<String, ConfigurationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if path != "~" && !path.starts_with("~/") {
+        return Ok(path.to_string());
+    }
+
+    lookup("HOME")
+        .map(|home| format!("{}{}", home, &path[1..]))
+        .ok_or_else(|| ConfigurationError::UndefinedVariable("HOME".to_string(), path.to_string()))
+}
+
+fn expand_variables<F>(path: &str, lookup: &F) -> Result<String, ConfigurationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut expanded = String::new();
+    let mut remaining = path;
+    while let Some(index) = remaining.find('$') {
+        expanded.push_str(&remaining[..index]);
+        remaining = &remaining[index + 1..];
+
+        if let Some(rest) = remaining.strip_prefix('$') {
+            expanded.push('$');
+            remaining = rest;
+        } else if let Some(rest) = remaining.strip_prefix('{') {
+            let end = rest.find('}')
+                .ok_or_else(|| {
+                    ConfigurationError::InvalidPath(path.to_string(), "missing closing brace for variable".to_string())
+                })?;
+            let (name, default) = match rest[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&rest[..end], None),
+            };
+            expanded.push_str(&lookup_variable(path, name, default, lookup)?);
+            remaining = &rest[end + 1..];
+        } else {
+            let end = remaining.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(remaining.len());
+            if end == 0 {
+                expanded.push('$');
+                continue;
+            }
+            expanded.push_str(&lookup_variable(path, &remaining[..end], None, lookup)?);
+            remaining = &remaining[end..];
+        }
+    }
+    expanded.push_str(remaining);
+
+    Ok(expanded)
+}
+
+/// Variables that are defined but empty are treated as undefined when a default is available,
+/// same as with `${VAR:-default}` in the shell.
+fn lookup_variable<F>(path: &str, name: &str, default: Option<&str>, lookup: &F) -> Result<String, ConfigurationError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match (lookup(name), default) {
+        (Some(value), Some(default)) if value.is_empty() => Ok(default.to_string()),
+        (Some(value), _) => Ok(value),
+        (None, Some(default)) => Ok(default.to_string()),
+        (None, None) => Err(ConfigurationError::UndefinedVariable(name.to_string(), path.to_string())),
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "HOME" => Some("/home/linker".to_string()),
+            "CACHE" => Some("/var/cache".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        }
+    }
+
+    fn expand(path: &str) -> Result<String, ConfigurationError> {
+        expand_path_with_lookup(path, Path::new("/etc/linker"), lookup)
+    }
+
+    #[test]
+    fn expand_path_with_absolute_path() {
+        assert_eq!(Ok("/var/cache/pacman/pkg".to_string()), expand("/var/cache/pacman/pkg"))
+    }
+
+    #[test]
+    fn expand_path_with_relative_path() {
+        assert_eq!(Ok("/etc/linker/pkg".to_string()), expand("pkg"));
+        assert_eq!(Ok("/etc/pkg".to_string()), expand("../pkg"));
+        assert_eq!(Ok("/etc/linker/pkg".to_string()), expand("./pkg"));
+        assert_eq!(Ok("/pkg".to_string()), expand("../../../pkg"))
+    }
+
+    #[test]
+    fn expand_path_with_home_directory() {
+        assert_eq!(Ok("/home/linker".to_string()), expand("~"));
+        assert_eq!(Ok("/home/linker/pkg".to_string()), expand("~/pkg"));
+        assert_eq!(Ok("/etc/linker/~pkg".to_string()), expand("~pkg"))
+    }
+
+    #[test]
+    fn expand_path_with_variable() {
+        assert_eq!(Ok("/var/cache/pacman/pkg".to_string()), expand("$CACHE/pacman/pkg"));
+        assert_eq!(Ok("/var/cache/pacman/pkg".to_string()), expand("${CACHE}/pacman/pkg"))
+    }
+
+    #[test]
+    fn expand_path_with_variable_default() {
+        assert_eq!(Ok("/var/cache/pacman/pkg".to_string()), expand("${CACHE:-/tmp}/pacman/pkg"));
+        assert_eq!(Ok("/tmp/pacman/pkg".to_string()), expand("${UNDEFINED:-/tmp}/pacman/pkg"));
+        assert_eq!(Ok("/tmp/pacman/pkg".to_string()), expand("${EMPTY:-/tmp}/pacman/pkg"))
+    }
+
+    #[test]
+    fn expand_path_with_escaped_variable() {
+        assert_eq!(Ok("/var/$CACHE".to_string()), expand("/var/$$CACHE"));
+        assert_eq!(Ok("/var/$".to_string()), expand("/var/$"))
+    }
+
+    #[test]
+    fn expand_path_with_undefined_variable() {
+        assert_eq!(
+            Err(ConfigurationError::UndefinedVariable("UNDEFINED".to_string(), "$UNDEFINED/pkg".to_string())),
+            expand("$UNDEFINED/pkg")
+        )
+    }
+
+    #[test]
+    fn expand_path_without_closing_brace() {
+        assert_eq!(
+            Err(
+                ConfigurationError::InvalidPath(
+                    "${CACHE/pkg".to_string(),
+                    "missing closing brace for variable".to_string(),
+                )
+            ),
+            expand("${CACHE/pkg")
+        )
+    }
+}
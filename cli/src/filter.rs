@@ -15,54 +15,104 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::path::{Path, PathBuf};
+
+use crate::configuration::{LinkMap, MatchStrategy};
+use crate::link::is_suffixed_link_path;
+use crate::match_link_maps::match_link_maps;
 use crate::node::Node;
 
+#[cfg(test)]
 pub fn filter(sources: &[Node], targets: &[Node]) -> Vec<Node> {
-    let source_path_for_targets = extract_source_path_for_targets(targets);
+    filter_with_link_maps(sources, targets, &[], MatchStrategy::Last)
+}
+
+/// Removes the source nodes that have already been linked, i.e. for nodes matching the link maps
+/// there is a link to the node at every link path the link maps would produce, and otherwise any
+/// link within the targets to the node.
+pub fn filter_with_link_maps(
+    sources: &[Node],
+    targets: &[Node],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+) -> Vec<Node> {
+    let links = extract_links_for_targets(targets);
     sources.iter()
-        .flat_map(|n| filter_linked_nodes(&n, &source_path_for_targets))
+        .flat_map(|n| filter_linked_nodes(n, &links, link_maps, match_strategy))
         .collect()
 }
 
-/// Extracts the source path from targets. As the targets should only be `Node::Link`
-/// it's the only type that we'll handle.
-fn extract_source_path_for_targets(targets: &[Node]) -> Vec<String> {
+/// Extracts the link path and source path from targets. As the targets should only be
+/// `Node::Link` it's the only type that we'll handle.
+fn extract_links_for_targets(targets: &[Node]) -> Vec<(PathBuf, PathBuf)> {
     targets.iter()
         .flat_map(|n| {
             match n {
                 Node::Leaf(_) => Vec::new(),
-                Node::Link(_, path) => vec![path.to_owned()],
+                Node::Link(link, path) => vec![(link.to_owned(), path.to_owned())],
+                Node::BrokenLink(_, _) => Vec::new(),
                 Node::Branch(_, _) => Vec::new(),
             }
         })
         .collect()
 }
 
-fn filter_linked_nodes(node: &Node, source_path_for_targets: &[String]) -> Vec<Node> {
-    if is_linked(node, source_path_for_targets) {
+fn filter_linked_nodes(
+    node: &Node,
+    links: &[(PathBuf, PathBuf)],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+) -> Vec<Node> {
+    if is_linked(node, links, link_maps, match_strategy) {
         Vec::new()
     } else {
         match node {
-            Node::Branch(_, _) => filter_branch(node, source_path_for_targets),
+            Node::Branch(_, _) => filter_branch(node, links, link_maps, match_strategy),
             _ => vec![node.to_owned()]
         }
     }
 }
 
-fn is_linked(node: &Node, source_path_for_targets: &[String]) -> bool {
-    match node {
-        Node::Leaf(path) => source_path_for_targets.contains(path),
-        Node::Link(_, source) => source_path_for_targets.contains(source),
-        Node::Branch(path, _) => source_path_for_targets.contains(path),
+fn is_linked(node: &Node, links: &[(PathBuf, PathBuf)], link_maps: &[LinkMap], match_strategy: MatchStrategy) -> bool {
+    let path = match node {
+        Node::Leaf(path) => path,
+        Node::Link(_, source) => source,
+        Node::BrokenLink(_, _) => return false,
+        Node::Branch(path, _) => path,
+    };
+
+    let expected_links = match_link_maps(node, link_maps, match_strategy);
+    if expected_links.is_empty() {
+        return links.iter().any(|(_, source)| source == path);
     }
+
+    expected_links.iter()
+        .all(|v| match v {
+            Node::Link(expected_path, _) => is_linked_at(expected_path, path, links),
+            _ => false,
+        })
 }
 
-fn filter_branch(node: &Node, source_path_for_targets: &[String]) -> Vec<Node> {
+/// A link created next to the link path by the `suffix` conflict policy also counts, otherwise
+/// every run would create yet another suffixed link.
+fn is_linked_at(link_path: &Path, source: &Path, links: &[(PathBuf, PathBuf)]) -> bool {
+    links.iter()
+        .any(|(link, link_source)| {
+            link_source == source && (link == link_path || is_suffixed_link_path(link, link_path))
+        })
+}
+
+fn filter_branch(
+    node: &Node,
+    links: &[(PathBuf, PathBuf)],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+) -> Vec<Node> {
     return match node {
         Node::Branch(path, nodes) => {
             if !nodes.is_empty() {
                 let remaining_nodes: Vec<Node> = nodes.iter()
-                    .flat_map(|n| filter_linked_nodes(n, source_path_for_targets))
+                    .flat_map(|n| filter_linked_nodes(n, links, link_maps, match_strategy))
                     .collect();
                 if !remaining_nodes.is_empty() {
                     vec![Node::Branch(path.to_owned(), remaining_nodes.to_owned())]
@@ -80,7 +130,10 @@ fn filter_branch(node: &Node, source_path_for_targets: &[String]) -> Vec<Node> {
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
-    use crate::filter::filter;
+    use std::path::PathBuf;
+
+    use crate::configuration::{LinkMap, MatchStrategy};
+    use crate::filter::{filter, filter_with_link_maps};
     use crate::node::Node;
 
     #[test]
@@ -99,11 +152,11 @@ mod tests {
     #[test]
     fn filter_with_source_leaf() {
         let sources: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
         ];
         let targets: Vec<Node> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
         ];
 
         let actual = filter(&sources, &targets);
@@ -114,12 +167,12 @@ mod tests {
     #[test]
     fn filter_with_linked_source_leaf() {
         let sources: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
         ];
         let targets: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/link".to_string(),
-                "/var/tmp/leaf".to_string(),
+                PathBuf::from("/var/tmp/link"),
+                PathBuf::from("/var/tmp/leaf"),
             ),
         ];
         let expected: Vec<Node> = Vec::new();
@@ -134,11 +187,11 @@ mod tests {
     #[test]
     fn filter_with_source_link() {
         let sources: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
         let targets: Vec<Node> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
 
         let actual = filter(&sources, &targets);
@@ -152,14 +205,14 @@ mod tests {
     fn filter_with_source_branch() {
         let sources: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             ),
         ];
         let targets: Vec<Node> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             ),
         ];
@@ -173,10 +226,10 @@ mod tests {
     fn filter_with_nested_source_branch() {
         let sources: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
                     Node::Branch(
-                        "/var/tmp/branch/child".to_string(),
+                        PathBuf::from("/var/tmp/branch/child"),
                         Vec::new(),
                     ),
                 ],
@@ -185,10 +238,10 @@ mod tests {
         let targets: Vec<Node> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
                     Node::Branch(
-                        "/var/tmp/branch/child".to_string(),
+                        PathBuf::from("/var/tmp/branch/child"),
                         Vec::new(),
                     ),
                 ],
@@ -204,10 +257,10 @@ mod tests {
     fn filter_with_linked_source_branch() {
         let sources: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
                     Node::Branch(
-                        "/var/tmp/branch/child".to_string(),
+                        PathBuf::from("/var/tmp/branch/child"),
                         Vec::new(),
                     ),
                 ],
@@ -215,8 +268,8 @@ mod tests {
         ];
         let targets: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/link".to_string(),
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/link"),
+                PathBuf::from("/var/tmp/branch"),
             ),
         ];
         let expected: Vec<Node> = Vec::new();
@@ -230,10 +283,10 @@ mod tests {
     fn filter_with_nested_linked_source_branch() {
         let sources: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
                     Node::Branch(
-                        "/var/tmp/branch/child".to_string(),
+                        PathBuf::from("/var/tmp/branch/child"),
                         Vec::new(),
                     ),
                 ],
@@ -241,8 +294,8 @@ mod tests {
         ];
         let targets: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/link".to_string(),
-                "/var/tmp/branch/child".to_string(),
+                PathBuf::from("/var/tmp/link"),
+                PathBuf::from("/var/tmp/branch/child"),
             ),
         ];
         let expected: Vec<Node> = Vec::new();
@@ -251,4 +304,73 @@ mod tests {
 
         assert_eq!(expected, actual)
     }
+
+    // Link maps
+
+    fn link_maps_matching_leaf() -> Vec<LinkMap> {
+        vec![
+            LinkMap::new("leaf".to_string(), "/var/tmp/targets-1".to_string()).unwrap(),
+            LinkMap::new("leaf".to_string(), "/var/tmp/targets-2".to_string()).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn filter_with_link_maps_with_source_leaf_linked_by_all_link_maps() {
+        let sources: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/sources/leaf")),
+        ];
+        let targets: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-1/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-2/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = filter_with_link_maps(&sources, &targets, &link_maps_matching_leaf(), MatchStrategy::All);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_with_link_maps_with_source_leaf_linked_by_one_of_the_link_maps() {
+        let sources: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/sources/leaf")),
+        ];
+        let targets: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-1/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/sources/leaf")),
+        ];
+
+        let actual = filter_with_link_maps(&sources, &targets, &link_maps_matching_leaf(), MatchStrategy::All);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_with_link_maps_with_source_leaf_linked_with_suffix() {
+        let sources: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/sources/leaf")),
+        ];
+        let targets: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-1/leaf~1"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = filter_with_link_maps(&sources, &targets, &link_maps_matching_leaf(), MatchStrategy::First);
+
+        assert_eq!(expected, actual)
+    }
 }
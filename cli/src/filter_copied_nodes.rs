@@ -0,0 +1,378 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::configuration::{LinkMap, LinkMode, LinkOptions, MatchStrategy};
+use crate::match_link_maps::match_link_maps_with_options;
+use crate::node::Node;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Removes the source leaves that have already been linked as a hard link, reflink or copy, i.e.
+/// every link path that the link maps would produce for the leaf already is linked.
+pub fn filter_copied_nodes(
+    sources: &[Node],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    link_options: &LinkOptions,
+) -> Vec<Node> {
+    sources.iter()
+        .filter_map(|n| filter_copied_node(n, link_maps, match_strategy, link_options))
+        .collect()
+}
+
+fn filter_copied_node(
+    node: &Node,
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    link_options: &LinkOptions,
+) -> Option<Node> {
+    match node {
+        Node::Leaf(_) if is_copied(node, link_maps, match_strategy, link_options) => None,
+        Node::Branch(path, children) if !children.is_empty() => {
+            let remaining_nodes: Vec<Node> = children.iter()
+                .filter_map(|n| filter_copied_node(n, link_maps, match_strategy, link_options))
+                .collect();
+            if !remaining_nodes.is_empty() {
+                Some(Node::Branch(path.to_owned(), remaining_nodes))
+            } else {
+                None
+            }
+        }
+        _ => Some(node.to_owned()),
+    }
+}
+
+/// Leaves linked only as symbolic links are left to be filtered by their links within the targets.
+fn is_copied(node: &Node, link_maps: &[LinkMap], match_strategy: MatchStrategy, link_options: &LinkOptions) -> bool {
+    let links = match_link_maps_with_options(node, link_maps, match_strategy, link_options);
+    let uses_copies = links.iter()
+        .any(|(_, options)| options.modes.iter().any(|v| *v != LinkMode::Symlink));
+
+    uses_copies && links.iter().all(|(link, options)| match link {
+        Node::Link(path, source) => is_linked(path, source, &options.modes),
+        _ => false,
+    })
+}
+
+/// Checks whether the entry at the link path is a link to the source that any of the modes could
+/// have created, i.e. a symbolic link to the source, or a hard link, reflink or copy of it.
+pub(crate) fn is_linked(path: &Path, source: &Path, modes: &[LinkMode]) -> bool {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    if metadata.is_symlink() {
+        return modes.contains(&LinkMode::Symlink) && is_same_path(path, source);
+    }
+
+    modes.iter().any(|v| *v != LinkMode::Symlink) && is_copy(path, &metadata, source)
+}
+
+fn is_same_path(lhs: &Path, rhs: &Path) -> bool {
+    match (fs::canonicalize(lhs), fs::canonicalize(rhs)) {
+        (Ok(lhs), Ok(rhs)) => lhs == rhs,
+        _ => false,
+    }
+}
+
+/// The entry is either the same inode as the source, or a regular file with the same content. A
+/// file with the same size and modification time as the source is assumed to have the same
+/// content, since copies and reflinks are created with the modification time of the source.
+fn is_copy(path: &Path, metadata: &Metadata, source: &Path) -> bool {
+    let source_metadata = match fs::metadata(source) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    if (metadata.dev(), metadata.ino()) == (source_metadata.dev(), source_metadata.ino()) {
+        return true;
+    }
+    if !metadata.is_file() || !source_metadata.is_file() || metadata.len() != source_metadata.len() {
+        return false;
+    }
+
+    let is_same_modified = (metadata.mtime(), metadata.mtime_nsec())
+        == (source_metadata.mtime(), source_metadata.mtime_nsec());
+    is_same_modified || is_same_content(path, source)
+}
+
+/// Compares the content in chunks, the files are expected to have the same size.
+fn is_same_content(lhs: &Path, rhs: &Path) -> bool {
+    let (mut lhs, mut rhs) = match (File::open(lhs), File::open(rhs)) {
+        (Ok(lhs), Ok(rhs)) => (lhs, rhs),
+        _ => return false,
+    };
+
+    let mut lhs_buffer = vec![0; CHUNK_SIZE];
+    let mut rhs_buffer = vec![0; CHUNK_SIZE];
+    loop {
+        let length = match read_chunk(&mut lhs, &mut lhs_buffer) {
+            Ok(length) => length,
+            Err(_) => return false,
+        };
+        match read_chunk(&mut rhs, &mut rhs_buffer) {
+            Ok(rhs_length) if rhs_length == length => {}
+            _ => return false,
+        }
+        if lhs_buffer[..length] != rhs_buffer[..length] {
+            return false;
+        }
+        if length < CHUNK_SIZE {
+            return true;
+        }
+    }
+}
+
+/// Fills the buffer unless the end of the file is reached, and returns the number of bytes read.
+fn read_chunk(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut length = 0;
+    while length < buffer.len() {
+        match file.read(&mut buffer[length..]) {
+            Ok(0) => break,
+            Ok(read) => length += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(length)
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn create_file_with_content(path: &Path, content: &str) -> PathBuf {
+        fs::write(path, content)
+            .unwrap_or_else(|_| panic!("Unable to create file at: {:?}", path));
+
+        path.to_path_buf()
+    }
+
+    fn create_directory_at_path(path: &Path) -> PathBuf {
+        fs::create_dir(path)
+            .expect("Unable to create directory");
+
+        path.to_path_buf()
+    }
+
+    fn set_modified_time(path: &Path, modified: SystemTime) {
+        File::options()
+            .write(true)
+            .open(path)
+            .and_then(|v| v.set_modified(modified))
+            .unwrap_or_else(|_| panic!("Unable to set modification time of: {:?}", path));
+    }
+
+    fn copy_link_maps(target: &Path) -> Vec<LinkMap> {
+        vec![
+            LinkMap::new("leaf.*".to_string(), target.to_str().unwrap().to_string())
+                .unwrap()
+                .with_modes(vec![LinkMode::Hardlink, LinkMode::Copy]),
+        ]
+    }
+
+    #[test]
+    fn filter_copied_nodes_without_links() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let sources: Vec<Node> = vec![
+            Node::Leaf(create_file_with_content(&path.join("leaf"), "leaf")),
+        ];
+        let expected = sources.clone();
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_hard_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let leaf = create_file_with_content(&path.join("leaf"), "leaf");
+        fs::hard_link(&leaf, target.join("leaf"))
+            .expect("Unable to create hard link");
+        let sources: Vec<Node> = vec![
+            Node::Leaf(leaf),
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_copy_in_branch() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let branch = create_directory_at_path(&path.join("branch"));
+        let sources: Vec<Node> = vec![
+            Node::Branch(
+                branch,
+                vec![
+                    Node::Leaf(create_file_with_content(&path.join("branch").join("leaf-1"), "leaf-1")),
+                    Node::Leaf(create_file_with_content(&path.join("branch").join("leaf-2"), "leaf-2")),
+                ],
+            ),
+        ];
+        create_file_with_content(&target.join("leaf-1"), "leaf-1");
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                path.join("branch"),
+                vec![
+                    Node::Leaf(path.join("branch").join("leaf-2")),
+                ],
+            ),
+        ];
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_copy_at_other_path() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let sources: Vec<Node> = vec![
+            Node::Leaf(create_file_with_content(&path.join("leaf-1"), "")),
+            Node::Leaf(create_file_with_content(&path.join("leaf-2"), "")),
+        ];
+        create_file_with_content(&target.join("leaf-1"), "");
+        let expected: Vec<Node> = vec![
+            Node::Leaf(path.join("leaf-2")),
+        ];
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_different_content_of_same_size() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let sources: Vec<Node> = vec![
+            Node::Leaf(create_file_with_content(&path.join("leaf"), "leaf-1")),
+        ];
+        create_file_with_content(&target.join("leaf"), "leaf-2");
+        set_modified_time(&target.join("leaf"), UNIX_EPOCH);
+        let expected = sources.clone();
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_copy_larger_than_chunk() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let content = "leaf".repeat(CHUNK_SIZE);
+        let sources: Vec<Node> = vec![
+            Node::Leaf(create_file_with_content(&path.join("leaf-1"), &content)),
+            Node::Leaf(create_file_with_content(&path.join("leaf-2"), &content)),
+        ];
+        create_file_with_content(&target.join("leaf-1"), &content);
+        create_file_with_content(&target.join("leaf-2"), &format!("{}LEAF", &content[..content.len() - 4]));
+        set_modified_time(&target.join("leaf-1"), UNIX_EPOCH);
+        set_modified_time(&target.join("leaf-2"), UNIX_EPOCH);
+        let expected: Vec<Node> = vec![
+            Node::Leaf(path.join("leaf-2")),
+        ];
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_copy_with_same_modified_time() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let leaf = create_file_with_content(&path.join("leaf"), "leaf-1");
+        create_file_with_content(&target.join("leaf"), "leaf-2");
+        set_modified_time(&leaf, UNIX_EPOCH);
+        set_modified_time(&target.join("leaf"), UNIX_EPOCH);
+        let sources: Vec<Node> = vec![
+            Node::Leaf(leaf),
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let link_maps = copy_link_maps(&target);
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_copied_nodes_with_symbolic_link_mode() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let target = create_directory_at_path(&path.join("target"));
+        let sources: Vec<Node> = vec![
+            Node::Leaf(create_file_with_content(&path.join("leaf"), "leaf")),
+        ];
+        create_file_with_content(&target.join("leaf"), "leaf");
+        let link_maps = vec![
+            LinkMap::new("leaf".to_string(), target.to_str().unwrap().to_string()).unwrap(),
+        ];
+        let expected = sources.clone();
+
+        let actual = filter_copied_nodes(&sources, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+}
@@ -15,34 +15,83 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
 use log::warn;
+
+use crate::configuration::{Exclude, ExcludeType};
 use crate::node::Node;
 
-pub fn filter_source_nodes(nodes: &[Node], excludes: &[String]) -> Vec<Node> {
-    return recursive_exclusion_for_nodes(nodes, excludes);
+#[cfg(test)]
+pub fn filter_source_nodes(nodes: &[Node], excludes: &[Exclude]) -> Vec<Node> {
+    filter_source_nodes_with_followed_links(nodes, &[], excludes)
+}
+
+/// Filters the nodes where the links that were followed while scanning are excluded as links,
+/// i.e. a followed link is matched by the excludes with the type `link`.
+pub fn filter_source_nodes_with_followed_links(
+    nodes: &[Node],
+    followed_links: &[(PathBuf, PathBuf)],
+    excludes: &[Exclude],
+) -> Vec<Node> {
+    match nodes.first() {
+        Some(node) => {
+            recursive_exclusion_for_nodes(extract_root_from_node(node), nodes, followed_links, excludes)
+        }
+        None => Vec::new(),
+    }
 }
 
-fn recursive_exclusion_for_nodes(nodes: &[Node], excludes: &[String]) -> Vec<Node> {
+/// Extracts the source root, i.e. the parent directory, from a top-level node. Since the
+/// top-level nodes are collected from the same directory they all share the same root.
+fn extract_root_from_node(node: &Node) -> &Path {
+    extract_path_from_node(node)
+        .parent()
+        .unwrap_or(Path::new(""))
+}
+
+fn recursive_exclusion_for_nodes(
+    root: &Path,
+    nodes: &[Node],
+    followed_links: &[(PathBuf, PathBuf)],
+    excludes: &[Exclude],
+) -> Vec<Node> {
     return nodes.iter()
-        .map(|n| recursive_exclusion_for_node(n, excludes))
-        .filter(|n| exclude(n, excludes))
+        .map(|n| recursive_exclusion_for_node(root, n, followed_links, excludes))
+        .filter(|n| exclude(root, n, followed_links, excludes))
         .collect();
 }
 
-fn recursive_exclusion_for_node(node: &Node, excludes: &[String]) -> Node {
+fn recursive_exclusion_for_node(
+    root: &Path,
+    node: &Node,
+    followed_links: &[(PathBuf, PathBuf)],
+    excludes: &[Exclude],
+) -> Node {
     return match node {
         Node::Branch(path, nodes) => {
-            Node::Branch(path.clone(), recursive_exclusion_for_nodes(nodes, &excludes))
+            Node::Branch(path.clone(), recursive_exclusion_for_nodes(root, nodes, followed_links, excludes))
         }
         Node::Leaf(_) => node.to_owned(),
-        Node::Link(_, _) => node.to_owned()
+        Node::Link(_, _) => node.to_owned(),
+        Node::BrokenLink(_, _) => node.to_owned(),
     };
 }
 
-fn exclude(node: &Node, excludes: &[String]) -> bool {
+fn exclude(root: &Path, node: &Node, followed_links: &[(PathBuf, PathBuf)], excludes: &[Exclude]) -> bool {
     let value = extract_basename_from_node(node);
     return match value {
-        Some(basename) => !excludes.contains(&basename.to_lowercase()),
+        Some(basename) => {
+            let node_type = if is_followed_link(node, followed_links) {
+                ExcludeType::Link
+            } else {
+                extract_type_from_node(node)
+            };
+            let relative_path = extract_relative_path_from_node(root, node);
+            !excludes.iter()
+                .any(|e| e.is_match(node_type, basename, relative_path))
+        }
         None => {
             warn!("Unable to extract basename from {:?}", node);
             true
@@ -50,29 +99,53 @@ fn exclude(node: &Node, excludes: &[String]) -> bool {
     };
 }
 
-fn extract_basename_from_node(node: &Node) -> Option<String> {
-    let path = match node {
+fn is_followed_link(node: &Node, followed_links: &[(PathBuf, PathBuf)]) -> bool {
+    let path = extract_path_from_node(node);
+    followed_links.iter()
+        .any(|(link, _)| link == path)
+}
+
+fn extract_type_from_node(node: &Node) -> ExcludeType {
+    match node {
+        Node::Branch(_, _) => ExcludeType::Branch,
+        Node::Leaf(_) => ExcludeType::Leaf,
+        Node::Link(_, _) => ExcludeType::Link,
+        Node::BrokenLink(_, _) => ExcludeType::Link,
+    }
+}
+
+fn extract_path_from_node(node: &Node) -> &Path {
+    match node {
         Node::Branch(path, _) => path,
         Node::Leaf(path) => path,
         Node::Link(path, _) => path,
-    };
+        Node::BrokenLink(path, _) => path,
+    }
+}
+
+fn extract_basename_from_node(node: &Node) -> Option<&OsStr> {
+    extract_path_from_node(node).file_name()
+}
 
-    return path.split("/")
-        .last()
-        .map(|v| v.to_string())
-        .to_owned();
+fn extract_relative_path_from_node<'a>(root: &Path, node: &'a Node) -> &'a Path {
+    let path = extract_path_from_node(node);
+    path.strip_prefix(root)
+        .unwrap_or(path)
 }
 
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
-    use crate::filter_source_nodes::filter_source_nodes;
+    use std::path::PathBuf;
+
+    use crate::configuration::{Exclude, ExcludeScope, ExcludeType};
+    use crate::filter_source_nodes::{filter_source_nodes, filter_source_nodes_with_followed_links};
     use crate::node::Node;
 
     #[test]
     fn filter_source_nodes_without_nodes() {
         let nodes: Vec<Node> = Vec::new();
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = Vec::new();
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -83,11 +156,11 @@ mod tests {
     #[test]
     fn filter_source_nodes_with_leaf() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string())
+            Node::Leaf(PathBuf::from("/var/tmp/leaf"))
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string())
+            Node::Leaf(PathBuf::from("/var/tmp/leaf"))
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -98,13 +171,13 @@ mod tests {
     #[test]
     fn filter_source_nodes_with_leaves() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-1".to_string()),
-            Node::Leaf("/var/tmp/leaf-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-1".to_string()),
-            Node::Leaf("/var/tmp/leaf-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -115,14 +188,14 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_leaf() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-1".to_string()),
-            Node::Leaf("/var/tmp/leaf-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf-1".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf-1".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-2".to_string())
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2"))
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -133,14 +206,14 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_uppercase_leaf() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/LEAF-1".to_string()),
-            Node::Leaf("/var/tmp/LEAF-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf-1".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf-1".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/LEAF-2".to_string())
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-2"))
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -151,12 +224,12 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_leaves() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-1".to_string()),
-            Node::Leaf("/var/tmp/leaf-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf-1".to_string(),
-            "leaf-2".to_string(),
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf-1".to_string()).unwrap(),
+            Exclude::new("leaf-2".to_string()).unwrap(),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -168,12 +241,12 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_uppercase_leaves() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/LEAF-1".to_string()),
-            Node::Leaf("/var/tmp/LEAF-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf-1".to_string(),
-            "leaf-2".to_string(),
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf-1".to_string()).unwrap(),
+            Exclude::new("leaf-2".to_string()).unwrap(),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -185,11 +258,11 @@ mod tests {
     #[test]
     fn filter_source_nodes_with_link() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -200,13 +273,13 @@ mod tests {
     #[test]
     fn filter_source_nodes_with_links() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/link-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/link-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/link-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/link-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/link-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/link-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -217,14 +290,14 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_link() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/link-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/link-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/link-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "link-1".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("link-1".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/link-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -235,14 +308,14 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_uppercase_link() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/LINK-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/LINK-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/LINK-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/LINK-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "link-1".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("link-1".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/LINK-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/LINK-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
 
         let actual = filter_source_nodes(&nodes, &excludes);
@@ -253,12 +326,12 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_links() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/link-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/link-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/link-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "link-1".to_string(),
-            "link-2".to_string(),
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("link-1".to_string()).unwrap(),
+            Exclude::new("link-2".to_string()).unwrap(),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -270,12 +343,12 @@ mod tests {
     #[test]
     fn filter_source_nodes_when_excluding_uppercase_links() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/LINK-1".to_string(), "/var/tmp/leaf-1".to_string()),
-            Node::Link("/var/tmp/LINK-2".to_string(), "/var/tmp/leaf-2".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/LINK-1"), PathBuf::from("/var/tmp/leaf-1")),
+            Node::Link(PathBuf::from("/var/tmp/LINK-2"), PathBuf::from("/var/tmp/leaf-2")),
         ];
-        let excludes: Vec<String> = vec![
-            "link-1".to_string(),
-            "link-2".to_string(),
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("link-1".to_string()).unwrap(),
+            Exclude::new("link-2".to_string()).unwrap(),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -288,14 +361,14 @@ mod tests {
     fn filter_source_nodes_without_empty_branch() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             )
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             )
         ];
@@ -309,18 +382,18 @@ mod tests {
     fn filter_source_nodes_with_branch_and_child() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf"))
                 ],
             )
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf"))
                 ],
             )
         ];
@@ -334,20 +407,20 @@ mod tests {
     fn filter_source_nodes_with_branch_and_children() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf-1".to_string()),
-                    Node::Leaf("/var/tmp/branch/leaf-2".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-1")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-2")),
                 ],
             )
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf-1".to_string()),
-                    Node::Leaf("/var/tmp/branch/leaf-2".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-1")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-2")),
                 ],
             )
         ];
@@ -361,22 +434,22 @@ mod tests {
     fn filter_source_nodes_with_empty_branches() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 Vec::new(),
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 Vec::new(),
             ),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 Vec::new(),
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 Vec::new(),
             ),
         ];
@@ -390,30 +463,30 @@ mod tests {
     fn filter_source_nodes_with_branches_and_child() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-1/leaf-1".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-1"))
                 ],
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-2/leaf-2".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-2"))
                 ],
             ),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-1/leaf-1".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-1"))
                 ],
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-2/leaf-2".to_string())
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-2"))
                 ],
             ),
         ];
@@ -427,34 +500,34 @@ mod tests {
     fn filter_source_nodes_with_branches_and_children() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-1/leaf-1".to_string()),
-                    Node::Leaf("/var/tmp/branch-1/leaf-2".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-1")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-2")),
                 ],
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-2/leaf-3".to_string()),
-                    Node::Leaf("/var/tmp/branch-2/leaf-4".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-3")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-4")),
                 ],
             ),
         ];
-        let excludes: Vec<String> = Vec::new();
+        let excludes: Vec<Exclude> = Vec::new();
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-1/leaf-1".to_string()),
-                    Node::Leaf("/var/tmp/branch-1/leaf-2".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-1")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf-2")),
                 ],
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 vec![
-                    Node::Leaf("/var/tmp/branch-2/leaf-3".to_string()),
-                    Node::Leaf("/var/tmp/branch-2/leaf-4".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-3")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf-4")),
                 ],
             ),
         ];
@@ -468,20 +541,20 @@ mod tests {
     fn filter_source_nodes_when_excluding_branch() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 Vec::new(),
             ),
             Node::Branch(
-                "/var/tmp/branch-2".to_string(),
+                PathBuf::from("/var/tmp/branch-2"),
                 Vec::new(),
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "branch-2".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("branch-2".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch-1".to_string(),
+                PathBuf::from("/var/tmp/branch-1"),
                 Vec::new(),
             ),
         ];
@@ -495,20 +568,20 @@ mod tests {
     fn filter_source_nodes_when_excluding_uppercase_branch() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/BRANCH-1".to_string(),
+                PathBuf::from("/var/tmp/BRANCH-1"),
                 Vec::new(),
             ),
             Node::Branch(
-                "/var/tmp/BRANCH-2".to_string(),
+                PathBuf::from("/var/tmp/BRANCH-2"),
                 Vec::new(),
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "branch-2".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("branch-2".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/BRANCH-1".to_string(),
+                PathBuf::from("/var/tmp/BRANCH-1"),
                 Vec::new(),
             ),
         ];
@@ -522,14 +595,14 @@ mod tests {
     fn filter_source_nodes_when_excluding_branch_with_child() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
                 ],
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "branch".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("branch".to_string()).unwrap()
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -542,14 +615,14 @@ mod tests {
     fn filter_source_nodes_when_excluding_uppercase_branch_with_child() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/BRANCH".to_string(),
+                PathBuf::from("/var/tmp/BRANCH"),
                 vec![
-                    Node::Leaf("/var/tmp/BRANCH/leaf".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/BRANCH/leaf")),
                 ],
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "branch".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("branch".to_string()).unwrap()
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -562,18 +635,18 @@ mod tests {
     fn filter_source_nodes_when_excluding_child_in_branch() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/leaf".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
                 ],
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             ),
         ];
@@ -587,18 +660,18 @@ mod tests {
     fn filter_source_nodes_when_excluding_uppercase_child_in_branch() {
         let nodes: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Leaf("/var/tmp/branch/LEAF".to_string()),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/LEAF")),
                 ],
             ),
         ];
-        let excludes: Vec<String> = vec![
-            "leaf".to_string()
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf".to_string()).unwrap()
         ];
         let expected: Vec<Node> = vec![
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 Vec::new(),
             ),
         ];
@@ -607,4 +680,310 @@ mod tests {
 
         assert_eq!(expected, actual)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn filter_source_nodes_when_excluding_leaves_with_glob() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/name.pkg.tar.zst")),
+            Node::Leaf(PathBuf::from("/var/tmp/name.pkg.tar.zst.part")),
+            Node::Leaf(PathBuf::from("/var/tmp/name.pkg.tar.zst.sig")),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("*.part".to_string()).unwrap(),
+            Exclude::new("*.SIG".to_string()).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/name.pkg.tar.zst")),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_child_in_branch_with_glob() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-1")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-2")),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-10")),
+                ],
+            ),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("leaf-?".to_string()).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf-10")),
+                ],
+            ),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_child_in_branch_with_relative_path_glob() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch-1"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf")),
+                ],
+            ),
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch-2"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-2/leaf")),
+                ],
+            ),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("branch-[2-9]/*".to_string()).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch-1"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch-1/leaf")),
+                ],
+            ),
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch-2"),
+                Vec::new(),
+            ),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_nested_child_with_recursive_glob() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Branch(
+                        PathBuf::from("/var/tmp/branch/cache"),
+                        vec![
+                            Node::Leaf(PathBuf::from("/var/tmp/branch/cache/leaf")),
+                        ],
+                    ),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
+                ],
+            ),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::new("**/cache/*".to_string()).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Branch(
+                        PathBuf::from("/var/tmp/branch/cache"),
+                        Vec::new(),
+                    ),
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
+                ],
+            ),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_branch_with_type() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/build"),
+                Vec::new(),
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/build")),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_glob(
+                "build".to_string(),
+                Some(ExcludeType::Branch),
+                None,
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/build")),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_leaf_with_type() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/build"),
+                Vec::new(),
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/build")),
+            Node::Link(
+                PathBuf::from("/var/tmp/build"),
+                PathBuf::from("/var/tmp/sources/build"),
+            ),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_regex(
+                "^build$".to_string(),
+                Some(ExcludeType::Leaf),
+                None,
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/build"),
+                Vec::new(),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/build"),
+                PathBuf::from("/var/tmp/sources/build"),
+            ),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_with_regex() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-3")),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_regex(
+                "^leaf-[12]$".to_string(),
+                None,
+                None,
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/LEAF-3")),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_with_basename_scope() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
+                ],
+            ),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_regex(
+                "^branch/leaf$".to_string(),
+                None,
+                Some(ExcludeScope::Basename),
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
+                ],
+            ),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_when_excluding_with_relative_scope() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/branch/leaf")),
+                ],
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_regex(
+                "^branch/leaf$".to_string(),
+                None,
+                Some(ExcludeScope::Relative),
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                Vec::new(),
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
+        ];
+
+        let actual = filter_source_nodes(&nodes, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_source_nodes_with_followed_links_when_excluding_link_with_type() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/latest"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/latest/leaf")),
+                ],
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/linked-leaf")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
+        ];
+        let followed_links = vec![
+            (PathBuf::from("/var/tmp/latest"), PathBuf::from("/var/packages")),
+            (PathBuf::from("/var/tmp/linked-leaf"), PathBuf::from("/var/packages/leaf")),
+        ];
+        let excludes: Vec<Exclude> = vec![
+            Exclude::from_glob(
+                "latest".to_string(),
+                Some(ExcludeType::Link),
+                None,
+            ).unwrap(),
+            Exclude::from_regex(
+                "^linked-leaf$".to_string(),
+                Some(ExcludeType::Link),
+                Some(ExcludeScope::Relative),
+            ).unwrap(),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
+        ];
+
+        let actual = filter_source_nodes_with_followed_links(&nodes, &followed_links, &excludes);
+
+        assert_eq!(expected, actual)
+    }
+}
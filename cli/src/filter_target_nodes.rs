@@ -15,7 +15,11 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::path::{Path, PathBuf};
+
+use crate::configuration::Source;
 use crate::node::Node;
+use crate::prune::is_within_sources;
 
 pub fn filter_target_nodes(nodes: &[Node]) -> Vec<Node> {
     return nodes.iter()
@@ -34,13 +38,97 @@ fn filter_links(node: &Node) -> Vec<Node> {
         Node::Link(_, _) => vec![
             node.to_owned()
         ],
+        Node::BrokenLink(_, _) => Vec::new(),
     };
 }
 
+/// Broken links within the targets, i.e. links whose source no longer exists.
+pub fn filter_broken_links(nodes: &[Node]) -> Vec<Node> {
+    nodes.iter()
+        .flat_map(filter_broken_link)
+        .collect()
+}
+
+fn filter_broken_link(node: &Node) -> Vec<Node> {
+    match node {
+        Node::Branch(_, children) => filter_broken_links(children),
+        Node::BrokenLink(_, _) => vec![
+            node.to_owned()
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// Maps the source of links into the resolved path of a followed link back to the path of the
+/// followed link, i.e. the path of the node within the sources. Sources that are already within
+/// the sources are left as they are.
+pub fn map_followed_link_sources(
+    nodes: &[Node],
+    followed_links: &[(PathBuf, PathBuf)],
+    sources: &[Source],
+) -> Vec<Node> {
+    nodes.iter()
+        .map(|n| match n {
+            Node::Branch(path, children) => {
+                Node::Branch(path.to_owned(), map_followed_link_sources(children, followed_links, sources))
+            }
+            Node::Link(path, source) if !is_within_sources(source, sources) => {
+                Node::Link(path.to_owned(), map_followed_link_source(source, followed_links))
+            }
+            _ => n.to_owned(),
+        })
+        .collect()
+}
+
+/// Uses the followed link with the longest resolved path, i.e. the innermost followed link.
+fn map_followed_link_source(source: &Path, followed_links: &[(PathBuf, PathBuf)]) -> PathBuf {
+    followed_links.iter()
+        .filter_map(|(link, resolved)| source.strip_prefix(resolved).ok().map(|v| (link, resolved, v)))
+        .max_by_key(|(_, resolved, _)| resolved.as_os_str().len())
+        .map(|(link, _, relative_path)| {
+            if relative_path.as_os_str().is_empty() {
+                link.to_owned()
+            } else {
+                link.join(relative_path)
+            }
+        })
+        .unwrap_or_else(|| source.to_path_buf())
+}
+
+/// Resolves the source of the link from the path of a followed link within the sources to the
+/// resolved path of the followed link, i.e. the reverse of `map_followed_link_sources`, so that
+/// the link is created to the resolved path rather than to the followed link.
+pub fn resolve_followed_link_source(node: &Node, followed_links: &[(PathBuf, PathBuf)]) -> Node {
+    match node {
+        Node::Link(path, source) => Node::Link(path.to_owned(), resolve_followed_link_path(source, followed_links)),
+        _ => node.to_owned(),
+    }
+}
+
+/// Uses the followed link with the longest path, i.e. the innermost followed link.
+fn resolve_followed_link_path(source: &Path, followed_links: &[(PathBuf, PathBuf)]) -> PathBuf {
+    followed_links.iter()
+        .filter_map(|(link, resolved)| source.strip_prefix(link).ok().map(|v| (link, resolved, v)))
+        .max_by_key(|(link, _, _)| link.as_os_str().len())
+        .map(|(_, resolved, relative_path)| {
+            if relative_path.as_os_str().is_empty() {
+                resolved.to_owned()
+            } else {
+                resolved.join(relative_path)
+            }
+        })
+        .unwrap_or_else(|| source.to_path_buf())
+}
+
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
-    use crate::filter_target_nodes::filter_target_nodes;
+    use std::path::PathBuf;
+
+    use crate::configuration::Source;
+    use crate::filter_target_nodes::{
+        filter_broken_links, filter_target_nodes, map_followed_link_sources, resolve_followed_link_source,
+    };
     use crate::node::Node;
 
     #[test]
@@ -56,7 +144,7 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_leaf() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string())
+            Node::Leaf(PathBuf::from("/var/tmp/leaf"))
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -68,8 +156,8 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_leaves() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf-1".to_string()),
-            Node::Leaf("/var/tmp/leaf-2".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-1")),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf-2")),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -81,10 +169,10 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_link() {
         let nodes: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/link"), PathBuf::from("/var/tmp/leaf")),
         ];
 
         let actual = filter_target_nodes(&nodes);
@@ -95,16 +183,16 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_nested_link() {
         let nodes: Vec<Node> = vec![
-            Node::Leaf("/var/tmp/leaf".to_string()),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
             Node::Branch(
-                "/var/tmp/branch".to_string(),
+                PathBuf::from("/var/tmp/branch"),
                 vec![
-                    Node::Link("/var/tmp/branch/link".to_string(), "/var/tmp/leaf".to_string())
+                    Node::Link(PathBuf::from("/var/tmp/branch/link"), PathBuf::from("/var/tmp/leaf"))
                 ],
             ),
         ];
         let expected: Vec<Node> = vec![
-            Node::Link("/var/tmp/branch/link".to_string(), "/var/tmp/leaf".to_string()),
+            Node::Link(PathBuf::from("/var/tmp/branch/link"), PathBuf::from("/var/tmp/leaf")),
         ];
 
         let actual = filter_target_nodes(&nodes);
@@ -115,7 +203,7 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_branch() {
         let nodes: Vec<Node> = vec![
-            Node::Branch("/var/tmp/branch".to_string(), Vec::new())
+            Node::Branch(PathBuf::from("/var/tmp/branch"), Vec::new())
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -127,8 +215,8 @@ mod tests {
     #[test]
     fn filter_target_nodes_with_branches() {
         let nodes: Vec<Node> = vec![
-            Node::Branch("/var/tmp/branch-1".to_string(), Vec::new()),
-            Node::Branch("/var/tmp/branch-2".to_string(), Vec::new()),
+            Node::Branch(PathBuf::from("/var/tmp/branch-1"), Vec::new()),
+            Node::Branch(PathBuf::from("/var/tmp/branch-2"), Vec::new()),
         ];
         let expected: Vec<Node> = Vec::new();
 
@@ -136,4 +224,140 @@ mod tests {
 
         assert_eq!(expected, actual)
     }
+
+    #[test]
+    fn filter_target_nodes_with_broken_link() {
+        let nodes: Vec<Node> = vec![
+            Node::BrokenLink(PathBuf::from("/var/tmp/link"), PathBuf::from("../missing"))
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = filter_target_nodes(&nodes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn filter_broken_links_with_nested_broken_link() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/branch"),
+                vec![
+                    Node::BrokenLink(PathBuf::from("/var/tmp/branch/broken"), PathBuf::from("../missing")),
+                    Node::Link(PathBuf::from("/var/tmp/branch/link"), PathBuf::from("/var/tmp/leaf")),
+                ],
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/leaf")),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::BrokenLink(PathBuf::from("/var/tmp/branch/broken"), PathBuf::from("../missing")),
+        ];
+
+        let actual = filter_broken_links(&nodes);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn map_followed_link_sources_with_followed_links() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/targets/branch"),
+                vec![
+                    Node::Link(
+                        PathBuf::from("/var/tmp/targets/branch/leaf"),
+                        PathBuf::from("/var/packages/leaf"),
+                    ),
+                ],
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linked-leaf"),
+                PathBuf::from("/var/packages/linked-leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/outside"),
+                PathBuf::from("/var/outside"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/source"),
+                PathBuf::from("/var/tmp/sources/source"),
+            ),
+        ];
+        let followed_links = vec![
+            (PathBuf::from("/var/tmp/sources/latest"), PathBuf::from("/var/packages")),
+            (PathBuf::from("/var/tmp/sources/linked-leaf"), PathBuf::from("/var/packages/linked-leaf")),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/sources".to_string(), 0),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/targets/branch"),
+                vec![
+                    Node::Link(
+                        PathBuf::from("/var/tmp/targets/branch/leaf"),
+                        PathBuf::from("/var/tmp/sources/latest/leaf"),
+                    ),
+                ],
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linked-leaf"),
+                PathBuf::from("/var/tmp/sources/linked-leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/outside"),
+                PathBuf::from("/var/outside"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/source"),
+                PathBuf::from("/var/tmp/sources/source"),
+            ),
+        ];
+
+        let actual = map_followed_link_sources(&nodes, &followed_links, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn resolve_followed_link_source_with_followed_links() {
+        let followed_links = vec![
+            (PathBuf::from("/var/tmp/sources/latest"), PathBuf::from("/var/packages")),
+            (PathBuf::from("/var/tmp/sources/latest/linked-leaf"), PathBuf::from("/var/outside/linked-leaf")),
+        ];
+        let nodes: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/leaf"),
+                PathBuf::from("/var/tmp/sources/latest/leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linked-leaf"),
+                PathBuf::from("/var/tmp/sources/latest/linked-leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/source"),
+                PathBuf::from("/var/tmp/sources/source"),
+            ),
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/leaf"),
+                PathBuf::from("/var/packages/leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linked-leaf"),
+                PathBuf::from("/var/outside/linked-leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/source"),
+                PathBuf::from("/var/tmp/sources/source"),
+            ),
+        ];
+
+        let actual: Vec<Node> = nodes.iter()
+            .map(|v| resolve_followed_link_source(v, &followed_links))
+            .collect();
+
+        assert_eq!(expected, actual)
+    }
 }
@@ -0,0 +1,761 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs as unix_fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use json::{JsonValue, object};
+use log::{debug, error, info, warn};
+
+use crate::link::temporary_path;
+use crate::linker_error::LinkerError;
+
+const JOURNAL_EXTENSION: &str = "journal";
+
+/// A change to the targets, written to the journal before it's performed. Anything replaced or
+/// removed is kept as a backup within the journal directory so that it can be restored.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub(crate) enum Operation {
+    CreateDirectory(PathBuf),
+    CreateLink(PathBuf),
+    Replace(PathBuf, PathBuf),
+    Remove(PathBuf, PathBuf),
+    Rename(PathBuf, PathBuf),
+}
+
+impl Operation {
+    /// Path of the entry resulting from the change, if any.
+    pub(crate) fn path(&self) -> Option<&Path> {
+        match self {
+            Operation::CreateDirectory(path) => Some(path),
+            Operation::CreateLink(path) => Some(path),
+            Operation::Replace(path, _) => Some(path),
+            Operation::Remove(_, _) => None,
+            Operation::Rename(_, to) => Some(to),
+        }
+    }
+
+    pub(crate) fn revert(&self) -> io::Result<()> {
+        match self {
+            Operation::CreateDirectory(path) => fs::remove_dir(path),
+            Operation::CreateLink(path) => fs::remove_file(path),
+            Operation::Replace(path, backup) => restore_entry(backup, path),
+            Operation::Remove(path, backup) => restore_entry(backup, path),
+            Operation::Rename(from, to) => fs::rename(to, from),
+        }
+    }
+
+    fn to_json(&self) -> JsonValue {
+        match self {
+            Operation::CreateDirectory(path) => object! {
+                "operation": "createDirectory",
+                "path": path_to_json(path),
+            },
+            Operation::CreateLink(path) => object! {
+                "operation": "createLink",
+                "path": path_to_json(path),
+            },
+            Operation::Replace(path, backup) => object! {
+                "operation": "replace",
+                "path": path_to_json(path),
+                "backup": path_to_json(backup),
+            },
+            Operation::Remove(path, backup) => object! {
+                "operation": "remove",
+                "path": path_to_json(path),
+                "backup": path_to_json(backup),
+            },
+            Operation::Rename(from, to) => object! {
+                "operation": "rename",
+                "from": path_to_json(from),
+                "to": path_to_json(to),
+            },
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        let path = |key: &str| path_from_json(&value[key]);
+        match value["operation"].as_str()? {
+            "createDirectory" => Some(Operation::CreateDirectory(path("path")?)),
+            "createLink" => Some(Operation::CreateLink(path("path")?)),
+            "replace" => Some(Operation::Replace(path("path")?, path("backup")?)),
+            "remove" => Some(Operation::Remove(path("path")?, path("backup")?)),
+            "rename" => Some(Operation::Rename(path("from")?, path("to")?)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operation::CreateDirectory(path) => write!(f, "create directory {:?}", path),
+            Operation::CreateLink(path) => write!(f, "create link {:?}", path),
+            Operation::Replace(path, _) => write!(f, "replace {:?}", path),
+            Operation::Remove(path, _) => write!(f, "remove {:?}", path),
+            Operation::Rename(from, to) => write!(f, "rename {:?} to {:?}", from, to),
+        }
+    }
+}
+
+/// Paths are written as an array of their bytes, since they aren't required to be valid UTF-8.
+fn path_to_json(path: &Path) -> JsonValue {
+    JsonValue::Array(path.as_os_str().as_bytes().iter().map(|v| JsonValue::from(*v)).collect())
+}
+
+/// Paths written as a string are accepted as well, i.e. journals written by earlier versions.
+fn path_from_json(value: &JsonValue) -> Option<PathBuf> {
+    match value {
+        JsonValue::Array(values) => {
+            let bytes = values.iter()
+                .map(|v| v.as_u8())
+                .collect::<Option<Vec<u8>>>()?;
+            Some(PathBuf::from(OsString::from_vec(bytes)))
+        }
+        _ => value.as_str().map(PathBuf::from),
+    }
+}
+
+/// State of the entry resulting from a change, used to detect whether the entry has changed since.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub(crate) struct EntryState {
+    device: u64,
+    inode: u64,
+    size: u64,
+    modified: i64,
+    modified_nanoseconds: i64,
+}
+
+impl EntryState {
+    pub(crate) fn read(path: &Path) -> io::Result<Self> {
+        let metadata = path.symlink_metadata()?;
+        Ok(
+            EntryState {
+                device: metadata.dev(),
+                inode: metadata.ino(),
+                size: metadata.size(),
+                modified: metadata.mtime(),
+                modified_nanoseconds: metadata.mtime_nsec(),
+            }
+        )
+    }
+
+    /// Directories are only compared by identity, since they are modified by any change to their
+    /// entries.
+    pub(crate) fn is_unchanged(&self, path: &Path) -> bool {
+        match (path.symlink_metadata(), EntryState::read(path)) {
+            (Ok(metadata), Ok(state)) if metadata.is_dir() => {
+                self.device == state.device && self.inode == state.inode
+            }
+            (_, Ok(state)) => *self == state,
+            _ => false,
+        }
+    }
+
+    fn to_json(self) -> JsonValue {
+        object! {
+            "device": self.device,
+            "inode": self.inode,
+            "size": self.size,
+            "modified": self.modified,
+            "modifiedNanoseconds": self.modified_nanoseconds,
+        }
+    }
+
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        Some(
+            EntryState {
+                device: value["device"].as_u64()?,
+                inode: value["inode"].as_u64()?,
+                size: value["size"].as_u64()?,
+                modified: value["modified"].as_i64()?,
+                modified_nanoseconds: value["modifiedNanoseconds"].as_i64()?,
+            }
+        )
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub(crate) enum RunStatus {
+    Committed,
+    RolledBack,
+    Undone,
+    /// Some of the changes were unable to be reverted by an undo or a rollback.
+    PartiallyReverted,
+}
+
+impl FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "committed" => Ok(RunStatus::Committed),
+            "rolledBack" => Ok(RunStatus::RolledBack),
+            "undone" => Ok(RunStatus::Undone),
+            "partiallyReverted" => Ok(RunStatus::PartiallyReverted),
+            _ => Err(format!("Invalid run status {:?}", s)),
+        }
+    }
+}
+
+impl Display for RunStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunStatus::Committed => write!(f, "committed"),
+            RunStatus::RolledBack => write!(f, "rolledBack"),
+            RunStatus::Undone => write!(f, "undone"),
+            RunStatus::PartiallyReverted => write!(f, "partiallyReverted"),
+        }
+    }
+}
+
+/// Every change to the targets during a run goes through the journal. When the journal is enabled
+/// each change is written to the journal file before it's performed, and for a transactional run
+/// the completed changes can be rolled back in reverse order. The journal file is only created
+/// once the first change is written, i.e. a run without any changes leaves no journal behind.
+pub(crate) struct Journal {
+    path: Option<PathBuf>,
+    file: Option<File>,
+    transactional: bool,
+    backup_directory: PathBuf,
+    written_operations: usize,
+    operations: Vec<Operation>,
+}
+
+impl Journal {
+    /// Changes are performed without being written or kept for a rollback.
+    pub(crate) fn disabled() -> Self {
+        Journal {
+            path: None,
+            file: None,
+            transactional: false,
+            backup_directory: PathBuf::new(),
+            written_operations: 0,
+            operations: Vec::new(),
+        }
+    }
+
+    /// Creates the journal for a new run within the directory, e.g. `1700000000-1234.journal` with
+    /// the backups in `1700000000-1234/`.
+    pub(crate) fn create(directory: &Path, transactional: bool) -> io::Result<Self> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs())
+            .unwrap_or_default();
+        let run_id = format!("{}-{}", timestamp, process::id());
+
+        fs::create_dir_all(directory)?;
+        Ok(
+            Journal {
+                path: Some(journal_path(directory, &run_id)),
+                file: None,
+                transactional,
+                backup_directory: directory.join(run_id),
+                written_operations: 0,
+                operations: Vec::new(),
+            }
+        )
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    pub(crate) fn is_transactional(&self) -> bool {
+        self.transactional
+    }
+
+    pub(crate) fn create_directory(&mut self, path: &Path) -> Result<(), LinkerError> {
+        self.apply(Operation::CreateDirectory(path.to_path_buf()), || {
+            fs::create_dir(path)
+                .map_err(|e| LinkerError::UnableToCreateDirectory(path.to_path_buf(), e))
+        })
+    }
+
+    pub(crate) fn create_link<T>(
+        &mut self,
+        path: &Path,
+        create: impl FnOnce() -> Result<T, LinkerError>,
+    ) -> Result<T, LinkerError> {
+        self.apply(Operation::CreateLink(path.to_path_buf()), create)
+    }
+
+    /// Renames the replacement over the existing entry at the path.
+    pub(crate) fn replace(&mut self, path: &Path, replacement: &Path) -> Result<(), LinkerError> {
+        let map_err = |e| LinkerError::UnableToResolveConflict(path.to_path_buf(), e);
+        if !self.is_enabled() {
+            return fs::rename(replacement, path).map_err(map_err);
+        }
+
+        let backup = self.next_backup_path().map_err(LinkerError::UnableToWriteJournal)?;
+        self.apply(Operation::Replace(path.to_path_buf(), backup.clone()), || {
+            copy_entry(path, &backup).map_err(map_err)?;
+            fs::rename(replacement, path).map_err(map_err)
+        })
+    }
+
+    pub(crate) fn remove(&mut self, path: &Path) -> Result<(), LinkerError> {
+        let map_err = |e| LinkerError::UnableToRemoveEntry(path.to_path_buf(), e);
+        if !self.is_enabled() {
+            return remove_entry(path).map_err(map_err);
+        }
+
+        let backup = self.next_backup_path().map_err(LinkerError::UnableToWriteJournal)?;
+        self.apply(Operation::Remove(path.to_path_buf(), backup.clone()), || {
+            move_entry(path, &backup).map_err(map_err)
+        })
+    }
+
+    /// Same as `remove`, except that without a journal only an empty directory is removed.
+    pub(crate) fn remove_empty_directory(&mut self, path: &Path) -> Result<(), LinkerError> {
+        if !self.is_enabled() {
+            return fs::remove_dir(path)
+                .map_err(|e| LinkerError::UnableToRemoveEntry(path.to_path_buf(), e));
+        }
+
+        self.remove(path)
+    }
+
+    pub(crate) fn rename(&mut self, from: &Path, to: &Path) -> Result<(), LinkerError> {
+        self.apply(Operation::Rename(from.to_path_buf(), to.to_path_buf()), || {
+            fs::rename(from, to)
+                .map_err(|e| LinkerError::UnableToResolveConflict(from.to_path_buf(), e))
+        })
+    }
+
+    pub(crate) fn commit(&mut self) -> Result<(), LinkerError> {
+        if self.file.is_none() {
+            return Ok(());
+        }
+
+        self.write(object! { "status": RunStatus::Committed.to_string() })
+            .map_err(LinkerError::UnableToWriteJournal)?;
+        info!("Committed {} changes", self.operations.len());
+        Ok(())
+    }
+
+    /// Reverts the completed changes in reverse order, a change that can't be reverted is reported
+    /// and the rollback continues with the remaining changes.
+    pub(crate) fn rollback(&mut self) {
+        if !self.is_transactional() {
+            return;
+        }
+
+        warn!("Rolling back {} changes", self.operations.len());
+        self.operations.iter()
+            .rev()
+            .for_each(|v| match v.revert() {
+                Ok(_) => debug!("Reverted {}", v),
+                Err(e) => error!("Unable to revert {}: {}", v, e),
+            });
+        self.operations.clear();
+        if self.file.is_none() {
+            return;
+        }
+        if let Err(e) = self.write(object! { "status": RunStatus::RolledBack.to_string() }) {
+            error!("Unable to write rollback to journal: {}", e);
+        }
+    }
+
+    fn apply<T>(
+        &mut self,
+        operation: Operation,
+        perform: impl FnOnce() -> Result<T, LinkerError>,
+    ) -> Result<T, LinkerError> {
+        if !self.is_enabled() {
+            return perform();
+        }
+
+        let index = self.written_operations;
+        self.write(operation.to_json())
+            .map_err(LinkerError::UnableToWriteJournal)?;
+        self.written_operations += 1;
+
+        let value = perform()?;
+        let mut completed = object! { "completed": index };
+        if let Some(state) = operation.path().and_then(|v| EntryState::read(v).ok()) {
+            completed["state"] = state.to_json();
+        }
+        self.operations.push(operation);
+        self.write(completed)
+            .map_err(LinkerError::UnableToWriteJournal)?;
+        Ok(value)
+    }
+
+    fn write(&mut self, value: JsonValue) -> io::Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        match self.file {
+            Some(ref mut file) => {
+                writeln!(file, "{}", json::stringify(value))?;
+                file.sync_data()
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Creates the journal file, a transactional run is marked as such before any change so that
+    /// the run can be rolled back if it's interrupted.
+    fn open(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(path)?;
+        info!("Writing journal to {:?}", path);
+        if self.transactional {
+            writeln!(file, "{}", json::stringify(object! { "transactional": true }))?;
+        }
+        self.file = Some(file);
+        Ok(())
+    }
+
+    fn next_backup_path(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.backup_directory)?;
+        Ok(self.backup_directory.join(self.written_operations.to_string()))
+    }
+}
+
+/// A run as recorded by its journal, i.e. the completed changes in the order they were performed
+/// together with the state of the resulting entries.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub(crate) struct JournalRecord {
+    pub(crate) transactional: bool,
+    pub(crate) changes: Vec<(Operation, Option<EntryState>)>,
+    pub(crate) status: Option<RunStatus>,
+}
+
+pub(crate) fn journal_path(directory: &Path, run_id: &str) -> PathBuf {
+    directory.join(format!("{}.{}", run_id, JOURNAL_EXTENSION))
+}
+
+/// Lists the ids of the runs with a journal within the directory, ordered from the oldest run.
+pub(crate) fn find_run_ids(directory: &Path) -> io::Result<Vec<String>> {
+    let mut run_ids: Vec<String> = fs::read_dir(directory)?
+        .filter_map(|v| v.ok())
+        .map(|v| v.path())
+        .filter(|v| v.extension().is_some_and(|v| v == JOURNAL_EXTENSION))
+        .filter_map(|v| v.file_stem().and_then(|v| v.to_str()).map(|v| v.to_string()))
+        .collect();
+    run_ids.sort_by_key(|v| {
+        let (timestamp, process_id) = v.split_once('-').unwrap_or((v, ""));
+        (timestamp.parse::<u64>().unwrap_or_default(), process_id.parse::<u32>().unwrap_or_default())
+    });
+    Ok(run_ids)
+}
+
+/// Reads the journal, lines that can't be parsed are ignored, e.g. the last line of a journal
+/// from a run that was interrupted while writing it.
+pub(crate) fn read_journal(path: &Path) -> io::Result<JournalRecord> {
+    let mut operations: Vec<Operation> = Vec::new();
+    let mut changes: Vec<(Operation, Option<EntryState>)> = Vec::new();
+    let mut status: Option<RunStatus> = None;
+    let mut transactional = false;
+    for line in fs::read_to_string(path)?.lines() {
+        let value = match json::parse(line) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        if let Some(operation) = Operation::from_json(&value) {
+            operations.push(operation);
+        } else if let Some(operation) = value["completed"].as_usize().and_then(|v| operations.get(v)) {
+            changes.push((operation.to_owned(), EntryState::from_json(&value["state"])));
+        } else if let Some(v) = value["status"].as_str().and_then(|v| RunStatus::from_str(v).ok()) {
+            status = Some(v);
+        } else if let Some(v) = value["transactional"].as_bool() {
+            transactional = v;
+        }
+    }
+
+    Ok(JournalRecord { transactional, changes, status })
+}
+
+pub(crate) fn write_status(path: &Path, status: RunStatus) -> io::Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", json::stringify(object! { "status": status.to_string() }))?;
+    file.sync_data()
+}
+
+/// Removes the entry without following it, i.e. a link is removed rather than its source.
+pub(crate) fn remove_entry(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Copies the entry without following it, using a hard link when possible.
+fn copy_entry(from: &Path, to: &Path) -> io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.is_dir() {
+        fs::create_dir(to)?;
+        fs::set_permissions(to, metadata.permissions())?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_entry(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        return Ok(());
+    }
+
+    match fs::hard_link(from, to) {
+        Ok(_) => Ok(()),
+        Err(_) if metadata.file_type().is_symlink() => unix_fs::symlink(fs::read_link(from)?, to),
+        Err(_) => fs::copy(from, to).map(|_| ()),
+    }
+}
+
+/// Moves the entry, falling back to a copy when the destination is on another file system.
+fn move_entry(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_entry(from, to)?;
+            remove_entry(from)
+        }
+        result => result,
+    }
+}
+
+/// Restores the backup by renaming it over the path, when the backup is on another file system it
+/// is first copied next to the path so that the path is never missing.
+fn restore_entry(backup: &Path, path: &Path) -> io::Result<()> {
+    match fs::rename(backup, path) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let temporary_path = temporary_path(path);
+            copy_entry(backup, &temporary_path)?;
+            fs::rename(&temporary_path, path)?;
+            remove_entry(backup)
+        }
+        result => result,
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn create_file_with_content(path: &Path, content: &str) {
+        fs::write(path, content)
+            .unwrap_or_else(|_| panic!("Unable to create file at: {:?}", path));
+    }
+
+    fn read_entries(path: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(path)
+            .expect("Unable to read directory")
+            .filter_map(|v| v.ok())
+            .map(|v| v.file_name().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn rollback_with_completed_operations() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let targets_path = path.join("targets");
+        fs::create_dir(&targets_path)
+            .expect("Unable to create directory");
+        create_file_with_content(&targets_path.join("replaced"), "replaced");
+        create_file_with_content(&targets_path.join("removed"), "removed");
+        create_file_with_content(&targets_path.join("renamed"), "renamed");
+        create_file_with_content(&path.join("replacement"), "replacement");
+        let mut journal = Journal::create(&path.join("journal"), true)
+            .expect("Unable to create journal");
+        let expected = vec!["removed".to_string(), "renamed".to_string(), "replaced".to_string()];
+
+        journal.create_directory(&targets_path.join("branch")).unwrap();
+        journal.create_link(&targets_path.join("branch").join("link"), || {
+            unix_fs::symlink(path.join("replacement"), targets_path.join("branch").join("link"))
+                .map_err(LinkerError::UnableToCreateSymlink)
+        }).unwrap();
+        journal.replace(&targets_path.join("replaced"), &path.join("replacement")).unwrap();
+        journal.remove(&targets_path.join("removed")).unwrap();
+        journal.rename(&targets_path.join("renamed"), &targets_path.join("renamed~1")).unwrap();
+        journal.rollback();
+
+        let actual = read_entries(&targets_path);
+        assert_eq!(expected, actual);
+        assert_eq!("replaced", fs::read_to_string(targets_path.join("replaced")).unwrap());
+        assert_eq!("removed", fs::read_to_string(targets_path.join("removed")).unwrap())
+    }
+
+    #[test]
+    fn rollback_with_failed_operation() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_file_with_content(&path.join("existing"), "existing");
+        let mut journal = Journal::create(&path.join("journal"), true)
+            .expect("Unable to create journal");
+
+        let actual = journal.create_link(&path.join("existing"), || {
+            unix_fs::symlink(path.join("source"), path.join("existing"))
+                .map_err(LinkerError::UnableToCreateSymlink)
+        });
+        journal.rollback();
+
+        assert!(actual.is_err());
+        assert_eq!("existing", fs::read_to_string(path.join("existing")).unwrap())
+    }
+
+    #[test]
+    fn commit_with_journal() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_directory = path.join("journal");
+        let mut journal = Journal::create(&journal_directory, false)
+            .expect("Unable to create journal");
+
+        journal.create_directory(&path.join("branch")).unwrap();
+        journal.commit().unwrap();
+
+        let expected = JournalRecord {
+            transactional: false,
+            changes: vec![
+                (
+                    Operation::CreateDirectory(path.join("branch")),
+                    EntryState::read(&path.join("branch")).ok(),
+                ),
+            ],
+            status: Some(RunStatus::Committed),
+        };
+        let run_id = find_run_ids(&journal_directory)
+            .expect("Unable to find runs")
+            .remove(0);
+        let actual = read_journal(&journal_path(&journal_directory, &run_id))
+            .expect("Unable to read journal");
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn commit_with_journal_without_changes() {
+        let directory = create_temporary_directory();
+        let journal_directory = PathBuf::from(directory.path()).join("journal");
+        let mut journal = Journal::create(&journal_directory, true)
+            .expect("Unable to create journal");
+        let expected: Vec<String> = Vec::new();
+
+        journal.commit().unwrap();
+
+        let actual = find_run_ids(&journal_directory)
+            .expect("Unable to find runs");
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn commit_with_transactional_journal() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_directory = path.join("journal");
+        let mut journal = Journal::create(&journal_directory, true)
+            .expect("Unable to create journal");
+
+        journal.create_directory(&path.join("branch")).unwrap();
+        journal.commit().unwrap();
+
+        let run_id = find_run_ids(&journal_directory)
+            .expect("Unable to find runs")
+            .remove(0);
+        let actual = read_journal(&journal_path(&journal_directory, &run_id))
+            .expect("Unable to read journal");
+        assert!(actual.transactional);
+        assert_eq!(Some(RunStatus::Committed), actual.status)
+    }
+
+    #[test]
+    fn read_journal_with_interrupted_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_file = path.join("1700000000-1.journal");
+        create_file_with_content(&journal_file, r#"{"operation":"createLink","path":"/tmp/link-1"}
+{"completed":0}
+{"operation":"createLink","path":"/tmp/link-2"}
+{"comp"#);
+        let expected = JournalRecord {
+            transactional: false,
+            changes: vec![
+                (Operation::CreateLink(PathBuf::from("/tmp/link-1")), None),
+            ],
+            status: None,
+        };
+
+        let actual = read_journal(&journal_file)
+            .expect("Unable to read journal");
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn read_journal_with_non_utf8_path() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_directory = path.join("journal");
+        let link_path = path.join(OsStr::from_bytes(b"link-\xff"));
+        let mut journal = Journal::create(&journal_directory, false)
+            .expect("Unable to create journal");
+
+        journal.create_link(&link_path, || {
+            unix_fs::symlink(path.join("source"), &link_path)
+                .map_err(LinkerError::UnableToCreateSymlink)
+        }).unwrap();
+        journal.commit().unwrap();
+
+        let expected = JournalRecord {
+            transactional: false,
+            changes: vec![
+                (Operation::CreateLink(link_path.clone()), EntryState::read(&link_path).ok()),
+            ],
+            status: Some(RunStatus::Committed),
+        };
+        let run_id = find_run_ids(&journal_directory)
+            .expect("Unable to find runs")
+            .remove(0);
+        let actual = read_journal(&journal_path(&journal_directory, &run_id))
+            .expect("Unable to read journal");
+        assert_eq!(expected, actual)
+    }
+}
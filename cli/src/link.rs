@@ -15,78 +15,477 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::error::Error;
+use std::ffi::OsString;
 use std::fs;
+use std::fs::Permissions;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs as unix_fs;
-use std::path::PathBuf;
-use std::str::FromStr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use log::{debug, info, warn};
+use nix::errno::Errno;
+use nix::sys::signal;
+use nix::unistd::Pid;
 
+use crate::configuration::{ConflictPolicy, DirectoryOptions, LinkMode, LinkOptions};
+use crate::filter_copied_nodes::is_linked;
+use crate::journal::{Journal, remove_entry};
 use crate::linker_error::LinkerError;
 use crate::node::Node;
 
-pub fn create_link_for_node_dry_run(node: &Node) -> bool {
+const TEMPORARY_LINK_INFIX: &str = ".linker-tmp.";
+
+pub fn create_link_for_node_dry_run(
+    node: &Node,
+    options: &LinkOptions,
+    journal: &mut Journal,
+) -> Result<bool, LinkerError> {
     return match node {
         Node::Leaf(path) => {
-            warn!("Unable to create link with leaf path {}", path);
-            false
+            warn!("Unable to create link with leaf path {}", path.display());
+            Ok(false)
+        }
+        Node::Link(target, source) if is_linked(target, source, &options.modes) => {
+            debug!("Link {} -> {} already exists", target.display(), source.display());
+            Ok(true)
         }
         Node::Link(target, source) => {
-            info!("Creating symbolic link {} -> {}", target, source);
-            true
+            let link_path = match resolve_conflict(target, options.on_conflict, journal, true) {
+                Ok(Some(link_path)) => link_path.path().to_path_buf(),
+                Ok(None) => return Ok(false),
+                Err(e @ LinkerError::LinkPathAlreadyExists(_)) => return Err(e),
+                Err(e) if journal.is_transactional() => return Err(e),
+                Err(e) => {
+                    warn!("Unable to link {:?} -> {:?}: {}", target, source, e);
+                    return Ok(false);
+                }
+            };
+
+            if options.modes.first().is_some_and(|v| *v != LinkMode::Symlink) {
+                let modes = options.modes.iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<String>>()
+                    .join(",");
+                info!("Creating link {} -> {} using {}", link_path.display(), source.display(), modes);
+            } else if options.relative {
+                info!("Creating relative symbolic link {} -> {}", link_path.display(), source.display());
+            } else {
+                info!("Creating symbolic link {} -> {}", link_path.display(), source.display());
+            }
+            Ok(true)
+        }
+        Node::BrokenLink(path, _) => {
+            warn!("Unable to create link with broken link path {}", path.display());
+            Ok(false)
         }
         Node::Branch(path, _) => {
-            warn!("Unable to create link with branch path {}", path);
-            false
+            warn!("Unable to create link with branch path {}", path.display());
+            Ok(false)
         }
     };
 }
 
-pub fn create_link_for_node(node: &Node) -> bool {
+/// Failing to create the link is reported and the node is left unlinked, unless the run is
+/// transactional in which case the error is returned so that the run can be rolled back.
+pub fn create_link_for_node(node: &Node, options: &LinkOptions, journal: &mut Journal) -> Result<bool, LinkerError> {
     return match node {
         Node::Leaf(path) => {
-            warn!("Unable to create link with leaf path {}", path);
-            false
+            warn!("Unable to create link with leaf path {}", path.display());
+            Ok(false)
+        }
+        Node::Link(target, source) if is_linked(target, source, &options.modes) => {
+            debug!("Link {} -> {} already exists", target.display(), source.display());
+            Ok(true)
         }
         Node::Link(target, source) => {
-            match create_link(&target, &source) {
-                Ok(_) => {
-                    info!("Symbolic link {} -> {} was successfully created", target, source);
-                    true
-                }
+            let link_path = match resolve_conflict(target, options.on_conflict, journal, false) {
+                Ok(Some(link_path)) => link_path,
+                Ok(None) => return Ok(false),
+                Err(e @ LinkerError::LinkPathAlreadyExists(_)) => return Err(e),
+                Err(e) if journal.is_transactional() => return Err(e),
                 Err(e) => {
                     warn!("Unable to link {:?} -> {:?}: {}", target, source, e);
-                    false
+                    return Ok(false);
+                }
+            };
+
+            let result = match &link_path {
+                LinkPath::New(path) => create_link(path, source, options, journal),
+                LinkPath::Existing(path) => replace_link(path, source, options, journal),
+            };
+            let link_path = link_path.path();
+            match result {
+                Ok(LinkMode::Symlink) => {
+                    info!("Symbolic link {} -> {} was successfully created", link_path.display(), source.display());
+                    Ok(true)
+                }
+                Ok(mode) => {
+                    info!(
+                        "Link {} -> {} was successfully created using {}",
+                        link_path.display(),
+                        source.display(),
+                        mode
+                    );
+                    Ok(true)
+                }
+                Err(e) if journal.is_transactional() => Err(e),
+                Err(e) => {
+                    warn!("Unable to link {:?} -> {:?}: {}", link_path, source, e);
+                    Ok(false)
                 }
             }
         }
+        Node::BrokenLink(path, _) => {
+            warn!("Unable to create link with broken link path {}", path.display());
+            Ok(false)
+        }
         Node::Branch(path, _) => {
-            warn!("Unable to create link with branch path {}", path);
-            false
+            warn!("Unable to create link with branch path {}", path.display());
+            Ok(false)
         }
     };
 }
 
-fn create_link(target: &str, source: &str) -> Result<(), Box<dyn Error>> {
-    let target_path = PathBuf::from_str(target)?;
-    return match target_path.as_path().parent() {
-        Some(parent_path) => {
-            debug!("Check if path {:?} exists", parent_path);
-            if !parent_path.exists() {
-                debug!("Path {:?} do not exists, creating...", parent_path);
-                fs::create_dir(parent_path)?;
-                debug!("Path {:?} was successfully created", parent_path);
+/// Path at which the link is created, an existing entry is replaced by renaming the link over it.
+enum LinkPath {
+    New(PathBuf),
+    Existing(PathBuf),
+}
+
+impl LinkPath {
+    fn path(&self) -> &Path {
+        match self {
+            LinkPath::New(path) => path,
+            LinkPath::Existing(path) => path,
+        }
+    }
+}
+
+/// Resolves the conflict with an existing entry at the link path using the policy, and returns the
+/// path at which the link should be created, or `None` if the link is skipped. During a dry run the
+/// conflict is only reported.
+fn resolve_conflict(
+    target: &Path,
+    policy: ConflictPolicy,
+    journal: &mut Journal,
+    dry_run: bool,
+) -> Result<Option<LinkPath>, LinkerError> {
+    let metadata = match target.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(Some(LinkPath::New(target.to_path_buf()))),
+    };
+
+    match policy {
+        ConflictPolicy::Skip => {
+            warn!("Link path {:?} already exists, skipping", target);
+            Ok(None)
+        }
+        ConflictPolicy::Fail => Err(LinkerError::LinkPathAlreadyExists(target.to_path_buf())),
+        ConflictPolicy::Replace if metadata.is_dir() => {
+            // A directory can't be replaced by renaming a link over it, i.e. the path will be
+            // missing until the link has been created. Only an empty directory is removed since
+            // anything within it would otherwise be lost.
+            let map_err = |e| LinkerError::UnableToResolveConflict(target.to_path_buf(), e);
+            let is_empty = fs::read_dir(target)
+                .map(|mut v| v.next().is_none())
+                .map_err(map_err)?;
+            if !is_empty {
+                return Err(map_err(io::Error::from(io::ErrorKind::DirectoryNotEmpty)));
+            }
+
+            info!("Link path {:?} already exists as an empty directory, removing it", target);
+            if !dry_run {
+                journal.remove_empty_directory(target)?;
+            }
+            Ok(Some(LinkPath::New(target.to_path_buf())))
+        }
+        ConflictPolicy::Replace => {
+            info!("Link path {:?} already exists, replacing", target);
+            Ok(Some(LinkPath::Existing(target.to_path_buf())))
+        }
+        ConflictPolicy::Backup => {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|v| v.as_secs())
+                .unwrap_or_default();
+            let backup_path = append_to_file_name(target, &format!(".linker-bak.{}", timestamp));
+            let backup_path = match backup_path.symlink_metadata() {
+                Ok(_) => append_unused_suffix(&backup_path),
+                Err(_) => backup_path,
+            };
+            info!("Link path {:?} already exists, moving it to {:?}", target, backup_path);
+            if !dry_run {
+                journal.rename(target, &backup_path)?;
             }
+            Ok(Some(LinkPath::New(target.to_path_buf())))
+        }
+        ConflictPolicy::Suffix => {
+            let suffix_path = append_unused_suffix(target);
+            info!("Link path {:?} already exists, creating link at {:?}", target, suffix_path);
+            Ok(Some(LinkPath::New(suffix_path)))
+        }
+    }
+}
 
+/// Replaces the existing entry by creating the link under a temporary name within the same
+/// directory and renaming it over the entry, i.e. the path is never missing.
+fn replace_link(
+    target: &Path,
+    source: &Path,
+    options: &LinkOptions,
+    journal: &mut Journal,
+) -> Result<LinkMode, LinkerError> {
+    let temporary_path = temporary_path(target);
+    let mode = create_link_with_modes(&temporary_path, source, options)?;
 
-            debug!("Creating symbolic link {} -> {}...", target, source);
-            unix_fs::symlink(source.to_string(), target.to_string())
-                .map_err(|e| Box::new(LinkerError::UnableToCreateSymlink(e)))?;
+    debug!("Renaming {:?} to {:?}...", temporary_path, target);
+    if let Err(e) = journal.replace(target, &temporary_path) {
+        if let Err(e) = remove_entry(&temporary_path) {
+            warn!("Unable to remove temporary link {:?}: {}", temporary_path, e);
+        }
+        return Err(e);
+    }
+    Ok(mode)
+}
+
+/// Temporary links are named after the link and the id of the process, e.g.
+/// `.name.linker-tmp.1234`, which allows leftovers from interrupted runs to be recognised.
+pub(crate) fn temporary_path(path: &Path) -> PathBuf {
+    let mut file_name = OsString::from(".");
+    file_name.push(path.file_name().unwrap_or_default());
+    file_name.push(format!("{}{}", TEMPORARY_LINK_INFIX, process::id()));
+    path.with_file_name(file_name)
+}
+
+fn temporary_link_process_id(path: &Path) -> Option<i32> {
+    let (_, process_id) = path.file_name()?
+        .to_str()?
+        .strip_prefix('.')?
+        .rsplit_once(TEMPORARY_LINK_INFIX)?;
+
+    process_id.parse::<i32>()
+        .ok()
+        .filter(|v| *v > 0)
+}
+
+pub(crate) fn is_process_running(process_id: i32) -> bool {
+    !matches!(signal::kill(Pid::from_raw(process_id), None), Err(Errno::ESRCH))
+}
+
+/// Finds the temporary links within the target nodes left behind by runs that were interrupted
+/// before the link was renamed, i.e. the process that created the temporary link is no longer
+/// running. Only symbolic links are considered, a leftover hard link or copy can't be told apart
+/// from an entry that happens to use the same name.
+pub fn find_leftover_temporary_links(nodes: &[Node]) -> Vec<PathBuf> {
+    nodes.iter()
+        .flat_map(|v| match v {
+            Node::Branch(_, children) => find_leftover_temporary_links(children),
+            Node::Link(path, _) | Node::BrokenLink(path, _) if is_leftover_temporary_link(path) => {
+                vec![path.to_owned()]
+            }
+            _ => vec![],
+        })
+        .collect()
+}
+
+fn is_leftover_temporary_link(path: &Path) -> bool {
+    temporary_link_process_id(path)
+        .is_some_and(|v| !is_process_running(v))
+}
+
+pub fn remove_leftover_temporary_link_dry_run(path: &Path) {
+    info!("Removing leftover temporary link {:?}", path);
+}
+
+pub fn remove_leftover_temporary_link(path: &Path) {
+    match fs::remove_file(path) {
+        Ok(_) => info!("Leftover temporary link {:?} was successfully removed", path),
+        Err(e) => warn!("Unable to remove leftover temporary link {:?}: {}", path, e),
+    }
+}
+
+fn append_to_file_name(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name()
+        .map(|v| v.to_os_string())
+        .unwrap_or_default();
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Appends the first `~1`, `~2`, ... suffix for which nothing exists at the path yet.
+fn append_unused_suffix(path: &Path) -> PathBuf {
+    (1..)
+        .map(|v| append_to_file_name(path, &format!("~{}", v)))
+        .find(|v| v.symlink_metadata().is_err())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Checks whether the path is the link path with a `~1`, `~2`, ... suffix, i.e. where the `suffix`
+/// conflict policy creates the link when something else already exists at the link path.
+pub(crate) fn is_suffixed_link_path(path: &Path, link_path: &Path) -> bool {
+    if path.parent() != link_path.parent() {
+        return false;
+    }
+
+    match (path.file_name(), link_path.file_name()) {
+        (Some(name), Some(link_name)) => name.as_bytes()
+            .strip_prefix(link_name.as_bytes())
+            .and_then(|v| v.strip_prefix(b"~"))
+            .is_some_and(|v| !v.is_empty() && v.iter().all(u8::is_ascii_digit)),
+        _ => false,
+    }
+}
+
+/// Creates the link, and any missing directories, using the first of the configured modes that
+/// succeeds, and returns the mode.
+fn create_link(
+    target: &Path,
+    source: &Path,
+    options: &LinkOptions,
+    journal: &mut Journal,
+) -> Result<LinkMode, LinkerError> {
+    let parent_path = target.parent()
+        .ok_or_else(|| LinkerError::UnableToGetParentDirectory(target.to_path_buf()))?;
+    create_directories(parent_path, &options.directory, journal)?;
+
+    journal.create_link(target, || create_link_with_modes(target, source, options))
+}
+
+fn create_link_with_modes(target: &Path, source: &Path, options: &LinkOptions) -> Result<LinkMode, LinkerError> {
+    let (mode, fallback_modes) = options.modes.split_last()
+        .unwrap_or((&LinkMode::Symlink, &[]));
+    for fallback_mode in fallback_modes {
+        match create_link_with_mode(*fallback_mode, target, source, options) {
+            Ok(_) => return Ok(*fallback_mode),
+            Err(e) => {
+                debug!("Unable to create link {:?} using {}, trying next mode: {}", target, fallback_mode, e)
+            }
+        }
+    }
+    create_link_with_mode(*mode, target, source, options)?;
+    Ok(*mode)
+}
+
+fn create_link_with_mode(
+    mode: LinkMode,
+    target: &Path,
+    source: &Path,
+    options: &LinkOptions,
+) -> Result<(), LinkerError> {
+    match mode {
+        LinkMode::Symlink => {
+            let source_path = match target.parent() {
+                Some(parent_path) if options.relative => {
+                    relative_path(&canonicalize(parent_path), &canonicalize(source))
+                }
+                _ => source.to_path_buf(),
+            };
+
+            debug!("Creating symbolic link {:?} -> {:?}...", target, source_path);
+            unix_fs::symlink(source_path, target)
+                .map_err(LinkerError::UnableToCreateSymlink)
+        }
+        LinkMode::Hardlink => {
+            // A hard link to a link would link the link itself, e.g. for a followed source link.
+            let source = canonicalize(source);
+            debug!("Creating hard link {:?} -> {:?}...", target, source);
+            fs::hard_link(source, target)
+                .map_err(|e| LinkerError::UnableToCreateLink(mode, e))
+        }
+        LinkMode::Reflink => {
+            debug!("Creating reflink {:?} -> {:?}...", target, source);
+            reflink_copy::reflink(source, target)
+                .map_err(|e| LinkerError::UnableToCreateLink(mode, e))?;
+            copy_modified_time(source, target);
             Ok(())
         }
-        None => Err(Box::new(LinkerError::UnableToGetParentDirectory(target_path))),
-    };
+        LinkMode::Copy => {
+            debug!("Copying {:?} to {:?}...", source, target);
+            // Unlike the other modes `fs::copy` would replace an existing file.
+            if target.symlink_metadata().is_ok() {
+                let e = io::Error::from(io::ErrorKind::AlreadyExists);
+                return Err(LinkerError::UnableToCreateLink(mode, e));
+            }
+            fs::copy(source, target)
+                .map_err(|e| LinkerError::UnableToCreateLink(mode, e))?;
+            copy_modified_time(source, target);
+            Ok(())
+        }
+    }
+}
+
+/// Copies and reflinks keep the modification time of the source, which lets them be recognised
+/// as links of the source without comparing their content. Failing to do so is only reported,
+/// since the content is compared instead.
+fn copy_modified_time(source: &Path, target: &Path) {
+    let result = fs::metadata(source)
+        .and_then(|v| v.modified())
+        .and_then(|modified| {
+            fs::File::options()
+                .write(true)
+                .open(target)?
+                .set_modified(modified)
+        });
+    if let Err(e) = result {
+        debug!("Unable to set modification time of {:?}: {}", target, e);
+    }
+}
+
+/// Creates the missing directories, starting from the top-most missing directory, and applies
+/// the mode and ownership to each of the created directories.
+fn create_directories(path: &Path, options: &DirectoryOptions, journal: &mut Journal) -> Result<(), LinkerError> {
+    debug!("Check if path {:?} exists", path);
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(parent_path) = path.parent() {
+        create_directories(parent_path, options, journal)?;
+    }
+
+    debug!("Path {:?} do not exists, creating...", path);
+    match journal.create_directory(path) {
+        Err(LinkerError::UnableToCreateDirectory(_, e)) if e.kind() == io::ErrorKind::AlreadyExists => return Ok(()),
+        result => result?,
+    }
+    let map_err = |e| LinkerError::UnableToCreateDirectory(path.to_path_buf(), e);
+    if let Some(mode) = options.mode {
+        fs::set_permissions(path, Permissions::from_mode(mode)).map_err(map_err)?;
+    }
+    if options.owner.is_some() || options.group.is_some() {
+        unix_fs::chown(path, options.owner, options.group).map_err(map_err)?;
+    }
+    debug!("Path {:?} was successfully created", path);
+    Ok(())
+}
+
+/// Resolves symbolic links within the path, if possible, so that the relative path is computed
+/// between the real locations.
+fn canonicalize(path: &Path) -> PathBuf {
+    fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Builds the minimal relative path from the directory to the path, both are expected to be
+/// absolute paths.
+fn relative_path(directory: &Path, path: &Path) -> PathBuf {
+    let directory_components: Vec<Component> = directory.components().collect();
+    let path_components: Vec<Component> = path.components().collect();
+    let common_components = directory_components.iter()
+        .zip(path_components.iter())
+        .take_while(|(lhs, rhs)| lhs == rhs)
+        .count();
+
+    let parent_components = directory_components.len() - common_components;
+    let mut relative_path: PathBuf = (0..parent_components)
+        .map(|_| Component::ParentDir)
+        .collect();
+    path_components[common_components..].iter()
+        .for_each(|v| relative_path.push(v));
+    relative_path
 }
 
 //noinspection DuplicatedCode
@@ -94,11 +493,21 @@ fn create_link(target: &str, source: &str) -> Result<(), Box<dyn Error>> {
 mod tests {
     use std::fs;
     use std::fs::File;
+    use std::os::unix::fs as unix_fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
     use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::time::{SystemTime, UNIX_EPOCH};
 
     use tempfile::TempDir;
 
-    use crate::link::{create_link_for_node, create_link_for_node_dry_run};
+    use crate::configuration::{ConflictPolicy, DirectoryOptions, LinkMode, LinkOptions};
+    use crate::journal::Journal;
+    use crate::link::{
+        create_link_for_node, create_link_for_node_dry_run, find_leftover_temporary_links, is_suffixed_link_path,
+        relative_path,
+    };
+    use crate::linker_error::LinkerError;
     use crate::node::Node;
 
     fn create_temporary_directory() -> TempDir {
@@ -106,22 +515,18 @@ mod tests {
             .expect("Unable to create temporary directory")
     }
 
-    fn create_file(path: &PathBuf) -> String {
+    fn create_file(path: &PathBuf) -> PathBuf {
         File::create(path)
             .expect(&format!("Unable to create file at: {:?}", path.to_str()));
 
-        path.to_str()
-            .map(|v| v.to_string())
-            .expect(&format!("Unable to build path for file at: {:?}", path.to_str()))
+        path.to_owned()
     }
 
-    fn create_directory_at_path(path: &Path) -> String {
+    fn create_directory_at_path(path: &Path) -> PathBuf {
         fs::create_dir(path)
             .expect("Unable to create directory");
 
-        path.to_str()
-            .map(|v| v.to_string())
-            .expect("Unable to build path for directory")
+        path.to_path_buf()
     }
 
     // Create link (dry run)
@@ -129,10 +534,10 @@ mod tests {
 
     #[test]
     fn create_link_for_node_dry_run_with_leaf() {
-        let node = Node::Leaf("/tmp/leaf".to_string());
-        let expected = false;
+        let node = Node::Leaf(PathBuf::from("/tmp/leaf"));
+        let expected = Ok(false);
 
-        let actual = create_link_for_node_dry_run(&node);
+        let actual = create_link_for_node_dry_run(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -142,12 +547,12 @@ mod tests {
     #[test]
     fn create_link_for_node_dry_run_with_link() {
         let node = Node::Link(
-            "/tmp/link".to_string(),
-            "/tmp/leaf".to_string(),
+            PathBuf::from("/tmp/link"),
+            PathBuf::from("/tmp/leaf"),
         );
-        let expected = true;
+        let expected = Ok(true);
 
-        let actual = create_link_for_node_dry_run(&node);
+        let actual = create_link_for_node_dry_run(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -157,12 +562,12 @@ mod tests {
     #[test]
     fn create_link_for_node_dry_run_with_branch() {
         let node = Node::Branch(
-            "/tmp/branch".to_string(),
+            PathBuf::from("/tmp/branch"),
             vec![],
         );
-        let expected = false;
+        let expected = Ok(false);
 
-        let actual = create_link_for_node_dry_run(&node);
+        let actual = create_link_for_node_dry_run(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -175,9 +580,9 @@ mod tests {
         let directory = create_temporary_directory();
         let path = PathBuf::from(directory.path());
         let node = Node::Leaf(create_file(&path.join("leaf")));
-        let expected = false;
+        let expected = Ok(false);
 
-        let actual = create_link_for_node(&node);
+        let actual = create_link_for_node(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -189,14 +594,12 @@ mod tests {
         let directory = create_temporary_directory();
         let path = PathBuf::from(directory.path());
         let node: Node = Node::Link(
-            path.join("link").to_str()
-                .expect("Unable to build link path")
-                .to_string(),
+            path.join("link"),
             create_file(&path.join("leaf")),
         );
-        let expected = true;
+        let expected = Ok(true);
 
-        let actual = create_link_for_node(&node);
+        let actual = create_link_for_node(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -208,14 +611,12 @@ mod tests {
         create_directory_at_path(&path.join("branch"));
         let node: Node = Node::Link(
             path.join("branch")
-                .join("link").to_str()
-                .expect("Unable to build link path")
-                .to_string(),
+                .join("link"),
             create_file(&path.join("leaf")),
         );
-        let expected = true;
+        let expected = Ok(true);
 
-        let actual = create_link_for_node(&node);
+        let actual = create_link_for_node(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
@@ -226,18 +627,48 @@ mod tests {
         let path = PathBuf::from(directory.path());
         let node: Node = Node::Link(
             path.join("branch")
-                .join("link").to_str()
-                .expect("Unable to build link path")
-                .to_string(),
+                .join("link"),
             create_file(&path.join("leaf")),
         );
-        let expected = true;
+        let expected = Ok(true);
 
-        let actual = create_link_for_node(&node);
+        let actual = create_link_for_node(&node, &LinkOptions::default(), &mut Journal::disabled());
 
         assert_eq!(expected, actual)
     }
 
+    #[test]
+    fn create_link_for_node_with_link_in_without_existing_nested_branches() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let metadata = fs::metadata(&path)
+            .expect("Unable to read metadata for temporary directory");
+        let node: Node = Node::Link(
+            path.join("branch-1")
+                .join("branch-2")
+                .join("link"),
+            create_file(&path.join("leaf")),
+        );
+        let options = LinkOptions {
+            directory: DirectoryOptions {
+                mode: Some(0o750),
+                owner: Some(metadata.uid()),
+                group: Some(metadata.gid()),
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let expected = 0o750;
+        let actual = fs::metadata(path.join("branch-1").join("branch-2"))
+            .expect("Unable to read metadata for branch")
+            .permissions()
+            .mode() & 0o7777;
+        assert_eq!(expected, actual);
+        assert_eq!(expected, fs::metadata(path.join("branch-1")).unwrap().permissions().mode() & 0o7777)
+    }
+
     // - Branch
 
     #[test]
@@ -248,10 +679,450 @@ mod tests {
             create_directory_at_path(&path.join("branch")),
             Vec::new(),
         );
-        let expected = false;
+        let expected = Ok(false);
+
+        let actual = create_link_for_node(&node, &LinkOptions::default(), &mut Journal::disabled());
+
+        assert_eq!(expected, actual)
+    }
+
+    // Relative link
+
+    #[test]
+    fn relative_path_with_sibling_directory() {
+        let expected = PathBuf::from("../source-directory/leaf");
+
+        let actual = relative_path(Path::new("/var/tmp/target-directory"), Path::new("/var/tmp/source-directory/leaf"));
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn relative_path_with_nested_directory() {
+        let expected = PathBuf::from("../../sources/leaf");
+
+        let actual = relative_path(Path::new("/var/tmp/targets/branch"), Path::new("/var/tmp/sources/leaf"));
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn relative_path_with_same_directory() {
+        let expected = PathBuf::from("leaf");
+
+        let actual = relative_path(Path::new("/var/tmp"), Path::new("/var/tmp/leaf"));
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn is_suffixed_link_path_with_suffix() {
+        assert!(is_suffixed_link_path(Path::new("/var/tmp/link~1"), Path::new("/var/tmp/link")));
+        assert!(is_suffixed_link_path(Path::new("/var/tmp/link~12"), Path::new("/var/tmp/link")))
+    }
+
+    #[test]
+    fn is_suffixed_link_path_without_suffix() {
+        assert!(!is_suffixed_link_path(Path::new("/var/tmp/link"), Path::new("/var/tmp/link")));
+        assert!(!is_suffixed_link_path(Path::new("/var/tmp/link~"), Path::new("/var/tmp/link")));
+        assert!(!is_suffixed_link_path(Path::new("/var/tmp/link~a"), Path::new("/var/tmp/link")));
+        assert!(!is_suffixed_link_path(Path::new("/var/tmp/other/link~1"), Path::new("/var/tmp/link")))
+    }
+
+    #[test]
+    fn create_link_for_node_with_relative_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        create_directory_at_path(&path.join("sources"));
+        create_directory_at_path(&path.join("targets"));
+        let link_path = path.join("targets").join("link");
+        let node: Node = Node::Link(
+            link_path.clone(),
+            create_file(&path.join("sources").join("leaf")),
+        );
+        let options = LinkOptions {
+            relative: true,
+            ..Default::default()
+        };
+        let expected = PathBuf::from("../sources/leaf");
 
-        let actual = create_link_for_node(&node);
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let actual = fs::read_link(&link_path)
+            .expect("Unable to read link");
+        assert_eq!(expected, actual)
+    }
+
+    // Link modes
+
+    #[test]
+    fn create_link_for_node_with_hard_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = path.join("link");
+        let leaf = create_file(&path.join("leaf"));
+        let node: Node = Node::Link(
+            link_path.clone(),
+            leaf.clone(),
+        );
+        let options = LinkOptions {
+            modes: vec![LinkMode::Hardlink],
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let expected = fs::metadata(&leaf).unwrap().ino();
+        let actual = fs::symlink_metadata(&link_path).unwrap().ino();
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn create_link_for_node_with_copy() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = path.join("link");
+        let leaf = create_file(&path.join("leaf"));
+        fs::write(&leaf, "leaf")
+            .expect("Unable to write to leaf");
+        let node: Node = Node::Link(
+            link_path.clone(),
+            leaf.clone(),
+        );
+        let options = LinkOptions {
+            modes: vec![LinkMode::Copy],
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let expected = "leaf".to_string();
+        let actual = fs::read_to_string(&link_path).unwrap();
+        assert_eq!(expected, actual);
+        assert!(!fs::symlink_metadata(&link_path).unwrap().file_type().is_symlink());
+
+        let expected = fs::metadata(&leaf).unwrap().modified().unwrap();
+        let actual = fs::metadata(&link_path).unwrap().modified().unwrap();
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn create_link_for_node_with_copy_to_existing_file() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            modes: vec![LinkMode::Copy],
+            ..Default::default()
+        };
+        let expected = Ok(false);
+
+        let actual = create_link_for_node(&node, &options, &mut Journal::disabled());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn create_link_for_node_with_existing_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = path.join("link");
+        let source = create_file(&path.join("leaf"));
+        unix_fs::symlink(&source, &link_path)
+            .expect("Unable to create symbolic link");
+        let node: Node = Node::Link(link_path.clone(), source.clone());
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Fail,
+            ..Default::default()
+        };
+        let expected = Ok(true);
+
+        let actual = create_link_for_node(&node, &options, &mut Journal::disabled());
+
+        assert_eq!(expected, actual);
+        assert_eq!(source, fs::read_link(&link_path).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_fallback_mode() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = path.join("link");
+        let node: Node = Node::Link(
+            link_path.clone(),
+            create_directory_at_path(&path.join("branch")),
+        );
+        let options = LinkOptions {
+            modes: vec![LinkMode::Hardlink, LinkMode::Copy, LinkMode::Symlink],
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        assert!(fs::symlink_metadata(&link_path).unwrap().file_type().is_symlink())
+    }
+
+    // - Conflict
+
+    fn create_conflicting_node(path: &Path) -> Node {
+        let link_path = path.join("link");
+        fs::write(&link_path, "link")
+            .expect("Unable to create file at link path");
+
+        Node::Link(
+            link_path.clone(),
+            create_file(&path.join("leaf")),
+        )
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_skip() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Skip,
+            ..Default::default()
+        };
+        let expected = Ok(false);
+
+        let actual = create_link_for_node(&node, &options, &mut Journal::disabled());
+
+        assert_eq!(expected, actual);
+        assert_eq!("link", fs::read_to_string(path.join("link")).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_fail() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Fail,
+            ..Default::default()
+        };
+        let expected = Err(LinkerError::LinkPathAlreadyExists(path.join("link")));
+
+        let actual = create_link_for_node(&node, &options, &mut Journal::disabled());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_replace() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Replace,
+            ..Default::default()
+        };
+        let expected = path.join("leaf");
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let actual = fs::read_link(path.join("link"))
+            .expect("Unable to read link");
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_backup() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Backup,
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let backups: Vec<String> = fs::read_dir(&path)
+            .expect("Unable to read directory")
+            .filter_map(|v| v.ok())
+            .map(|v| v.file_name().to_string_lossy().to_string())
+            .filter(|v| v.starts_with("link.linker-bak."))
+            .collect();
+        assert_eq!(1, backups.len());
+        assert_eq!("link", fs::read_to_string(path.join(&backups[0])).unwrap());
+        assert_eq!(path.join("leaf"), fs::read_link(path.join("link")).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_backup_with_existing_backup() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_secs())
+            .unwrap();
+        let existing_backups: Vec<PathBuf> = (timestamp..timestamp + 3)
+            .map(|v| path.join(format!("link.linker-bak.{}", v)))
+            .collect();
+        existing_backups.iter()
+            .for_each(|v| fs::write(v, "backup").expect("Unable to write backup"));
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Backup,
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let backups: Vec<String> = fs::read_dir(&path)
+            .expect("Unable to read directory")
+            .filter_map(|v| v.ok())
+            .map(|v| v.file_name().to_string_lossy().to_string())
+            .filter(|v| v.starts_with("link.linker-bak.") && v.ends_with("~1"))
+            .collect();
+        assert_eq!(1, backups.len());
+        assert_eq!("link", fs::read_to_string(path.join(&backups[0])).unwrap());
+        existing_backups.iter()
+            .for_each(|v| assert_eq!("backup", fs::read_to_string(v).unwrap()));
+        assert_eq!(path.join("leaf"), fs::read_link(path.join("link")).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_conflict_using_suffix() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        create_file(&path.join("link~1"));
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Suffix,
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        assert_eq!("link", fs::read_to_string(path.join("link")).unwrap());
+        assert_eq!(path.join("leaf"), fs::read_link(path.join("link~2")).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_dry_run_with_conflict_using_replace() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let node = create_conflicting_node(&path);
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Replace,
+            ..Default::default()
+        };
+        let expected = Ok(true);
+
+        let actual = create_link_for_node_dry_run(&node, &options, &mut Journal::disabled());
+
+        assert_eq!(expected, actual);
+        assert_eq!("link", fs::read_to_string(path.join("link")).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_link_to_other_source_using_replace() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = path.join("link");
+        unix_fs::symlink(create_file(&path.join("other")), &link_path)
+            .expect("Unable to create symbolic link");
+        let node = Node::Link(
+            link_path.clone(),
+            create_file(&path.join("leaf")),
+        );
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Replace,
+            ..Default::default()
+        };
+        let expected = vec!["leaf".to_string(), "link".to_string(), "other".to_string()];
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        let mut actual: Vec<String> = fs::read_dir(&path)
+            .expect("Unable to read directory")
+            .filter_map(|v| v.ok())
+            .map(|v| v.file_name().to_string_lossy().to_string())
+            .collect();
+        actual.sort();
+        assert_eq!(expected, actual);
+        assert_eq!(path.join("leaf"), fs::read_link(&link_path).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_empty_directory_using_replace() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = create_directory_at_path(&path.join("link"));
+        let node = Node::Link(
+            link_path.clone(),
+            create_file(&path.join("leaf")),
+        );
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Replace,
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(true), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        assert_eq!(path.join("leaf"), fs::read_link(&link_path).unwrap())
+    }
+
+    #[test]
+    fn create_link_for_node_with_non_empty_directory_using_replace() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let link_path = create_directory_at_path(&path.join("link"));
+        create_file(&link_path.join("important"));
+        let node = Node::Link(
+            link_path.clone(),
+            create_file(&path.join("leaf")),
+        );
+        let options = LinkOptions {
+            on_conflict: ConflictPolicy::Replace,
+            ..Default::default()
+        };
+
+        assert_eq!(Ok(false), create_link_for_node(&node, &options, &mut Journal::disabled()));
+
+        assert!(link_path.join("important").is_file())
+    }
+
+    // Leftover temporary links
+
+    fn exited_process_id() -> u32 {
+        let mut child = Command::new("true")
+            .spawn()
+            .expect("Unable to spawn process");
+        child.wait()
+            .expect("Unable to wait for process");
+
+        child.id()
+    }
+
+    #[test]
+    fn find_leftover_temporary_links_with_interrupted_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let process_id = exited_process_id();
+        let leftover_link = path.join("branch").join(format!(".leaf.linker-tmp.{}", process_id));
+        let leftover_broken_link = path.join("branch").join(format!(".removed.linker-tmp.{}", process_id));
+        let nodes = vec![
+            Node::Leaf(path.join(format!(".copy.linker-tmp.{}", process_id))),
+            Node::Link(path.join(format!(".leaf.linker-tmp.{}", std::process::id())), path.join("leaf")),
+            Node::Branch(
+                path.join("branch"),
+                vec![
+                    Node::Link(leftover_link.clone(), path.join("leaf")),
+                    Node::BrokenLink(leftover_broken_link.clone(), path.join("removed")),
+                ],
+            ),
+        ];
+        let expected = vec![
+            leftover_link,
+            leftover_broken_link,
+        ];
+
+        let actual = find_leftover_temporary_links(&nodes);
 
         assert_eq!(expected, actual)
     }
 }
+
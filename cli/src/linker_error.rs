@@ -19,10 +19,25 @@ use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
 
+use crate::configuration::LinkMode;
+
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub(crate) enum LinkerError {
     UnableToCreateSymlink(std::io::Error),
+    UnableToCreateLink(LinkMode, std::io::Error),
     UnableToGetParentDirectory(PathBuf),
+    LinkPathAlreadyExists(PathBuf),
+    UnableToResolveConflict(PathBuf, std::io::Error),
+    UnableToCreateDirectory(PathBuf, std::io::Error),
+    UnableToRemoveEntry(PathBuf, std::io::Error),
+    UnableToCreateJournal(String),
+    UnableToWriteJournal(std::io::Error),
+    UnableToReadJournal(PathBuf, std::io::Error),
+    UnknownRun(String),
+    InvalidRunId(String),
+    NoRunToUndo,
+    RunAlreadyReverted(String),
 }
 
 impl Eq for LinkerError {}
@@ -33,9 +48,37 @@ impl PartialEq<Self> for LinkerError {
             (LinkerError::UnableToCreateSymlink(lhs), LinkerError::UnableToCreateSymlink(rhs)) => {
                 lhs.kind() == rhs.kind()
             }
+            (LinkerError::UnableToCreateLink(lhs_mode, lhs), LinkerError::UnableToCreateLink(rhs_mode, rhs)) => {
+                lhs_mode == rhs_mode && lhs.kind() == rhs.kind()
+            }
             (LinkerError::UnableToGetParentDirectory(lhs), LinkerError::UnableToGetParentDirectory(rhs)) => {
                 lhs == rhs
             }
+            (LinkerError::LinkPathAlreadyExists(lhs), LinkerError::LinkPathAlreadyExists(rhs)) => {
+                lhs == rhs
+            }
+            (LinkerError::UnableToResolveConflict(lhs_path, lhs), LinkerError::UnableToResolveConflict(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs.kind() == rhs.kind()
+            }
+            (LinkerError::UnableToCreateDirectory(lhs_path, lhs), LinkerError::UnableToCreateDirectory(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs.kind() == rhs.kind()
+            }
+            (LinkerError::UnableToRemoveEntry(lhs_path, lhs), LinkerError::UnableToRemoveEntry(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs.kind() == rhs.kind()
+            }
+            (LinkerError::UnableToCreateJournal(lhs), LinkerError::UnableToCreateJournal(rhs)) => {
+                lhs == rhs
+            }
+            (LinkerError::UnableToWriteJournal(lhs), LinkerError::UnableToWriteJournal(rhs)) => {
+                lhs.kind() == rhs.kind()
+            }
+            (LinkerError::UnableToReadJournal(lhs_path, lhs), LinkerError::UnableToReadJournal(rhs_path, rhs)) => {
+                lhs_path == rhs_path && lhs.kind() == rhs.kind()
+            }
+            (LinkerError::UnknownRun(lhs), LinkerError::UnknownRun(rhs)) => lhs == rhs,
+            (LinkerError::InvalidRunId(lhs), LinkerError::InvalidRunId(rhs)) => lhs == rhs,
+            (LinkerError::NoRunToUndo, LinkerError::NoRunToUndo) => true,
+            (LinkerError::RunAlreadyReverted(lhs), LinkerError::RunAlreadyReverted(rhs)) => lhs == rhs,
             _ => false
         }
     }
@@ -49,9 +92,45 @@ impl Display for LinkerError {
             LinkerError::UnableToCreateSymlink(e) => {
                 write!(f, "Unable to create symlink: {}", e)
             }
+            LinkerError::UnableToCreateLink(mode, e) => {
+                write!(f, "Unable to create link using {}: {}", mode, e)
+            }
             LinkerError::UnableToGetParentDirectory(path) => {
                 write!(f, "Unable to get parent directory from path {:?}", path)
             }
+            LinkerError::LinkPathAlreadyExists(path) => {
+                write!(f, "Link path {:?} already exists", path)
+            }
+            LinkerError::UnableToResolveConflict(path, e) => {
+                write!(f, "Unable to resolve conflict with existing link path {:?}: {}", path, e)
+            }
+            LinkerError::UnableToCreateDirectory(path, e) => {
+                write!(f, "Unable to create directory {:?}: {}", path, e)
+            }
+            LinkerError::UnableToRemoveEntry(path, e) => {
+                write!(f, "Unable to remove {:?}: {}", path, e)
+            }
+            LinkerError::UnableToCreateJournal(message) => {
+                write!(f, "Unable to create journal: {}", message)
+            }
+            LinkerError::UnableToWriteJournal(e) => {
+                write!(f, "Unable to write journal: {}", e)
+            }
+            LinkerError::UnableToReadJournal(path, e) => {
+                write!(f, "Unable to read journal {:?}: {}", path, e)
+            }
+            LinkerError::UnknownRun(run_id) => {
+                write!(f, "Unable to find journal for run {}", run_id)
+            }
+            LinkerError::InvalidRunId(run_id) => {
+                write!(f, "Invalid run id {:?}, expected the name of a journal without extension", run_id)
+            }
+            LinkerError::NoRunToUndo => {
+                write!(f, "Unable to find a run to undo")
+            }
+            LinkerError::RunAlreadyReverted(run_id) => {
+                write!(f, "Run {} has already been undone or rolled back", run_id)
+            }
         }
     }
 }
@@ -15,120 +15,515 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::Parser;
-use log::info;
-
-use crate::arguments::Arguments;
-use crate::collect_nodes::collect_nodes;
-use crate::configuration::{Configuration, LinkMap, read_configuration};
-use crate::filter::filter;
-use crate::filter_source_nodes::filter_source_nodes;
-use crate::filter_target_nodes::filter_target_nodes;
-use crate::link::{create_link_for_node, create_link_for_node_dry_run};
-use crate::match_link_maps::match_link_maps;
+use log::{error, info, warn};
+
+use crate::arguments::{Arguments, Command};
+use crate::check_configuration::check_configuration_file;
+use crate::collect_nodes::{ScanError, ScanOptions, ScanResult, collect_nodes_with_options, configure_thread_pool};
+use crate::configuration::{Configuration, LinkMode, LinkOptions, MatchStrategy, Target, read_configuration};
+use crate::filter::filter_with_link_maps;
+use crate::filter_copied_nodes::filter_copied_nodes;
+use crate::filter_source_nodes::filter_source_nodes_with_followed_links;
+use crate::filter_target_nodes::{
+    filter_broken_links, filter_target_nodes, map_followed_link_sources, resolve_followed_link_source,
+};
+use crate::journal::Journal;
+use crate::link::{
+    create_link_for_node, create_link_for_node_dry_run, find_leftover_temporary_links, remove_leftover_temporary_link,
+    remove_leftover_temporary_link_dry_run,
+};
+use crate::linker_error::LinkerError;
+use crate::match_link_maps::{find_link_map_conflicts, match_link_maps_with_options};
 use crate::node::Node;
+use crate::prune::{find_expected_links, find_prunable_links, prune_links};
+use crate::reconcile::{apply_changes, plan_changes};
+use crate::shadow_source_nodes::shadow_source_nodes;
+use crate::undo::{recover_interrupted_runs, undo_run};
 
 mod configuration;
+mod configuration_error;
+mod configuration_format;
+mod expand_path;
 mod node;
 mod collect_nodes;
 mod filter_source_nodes;
+mod shadow_source_nodes;
 mod filter_target_nodes;
+mod filter_copied_nodes;
 mod filter;
 mod match_link_maps;
 mod link;
+mod journal;
+mod undo;
+mod prune;
+mod reconcile;
 mod arguments;
 mod linker_error;
+mod check_configuration;
+
+/// Creates the link for the node, returns whether the link was created or an error if the run
+/// should be aborted.
+type CreateLink = fn(&Node, &LinkOptions, &mut Journal) -> Result<bool, LinkerError>;
+
+/// Outcome of a run, i.e. the nodes that were not linked, the broken links within the targets and
+/// the entries that were unable to be scanned.
+#[derive(Eq, PartialEq, Debug)]
+struct Report {
+    remaining_nodes: Vec<Node>,
+    broken_links: Vec<Node>,
+    scan_errors: Vec<ScanError>,
+}
+
+/// Exit code used when the configuration can't be read, same as `EX_CONFIG` from `sysexits.h`.
+const EXIT_CODE_CONFIGURATION_ERROR: i32 = 78;
 
 fn main() {
     env_logger::init();
 
     let arguments = Arguments::parse();
-    let configuration = read_configuration(&arguments.configuration);
+    if let Some(Command::Check) = arguments.command {
+        check(&arguments);
+        return;
+    }
+    if let Err(e) = configure_thread_pool(arguments.jobs) {
+        warn!("Unable to configure {} jobs, using the default: {}", arguments.jobs, e);
+    }
+
+    let configuration = match read_configuration(&arguments.configuration) {
+        Ok(configuration) => configuration,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(EXIT_CODE_CONFIGURATION_ERROR);
+        }
+    };
+    if let Some(Command::Undo { run_id }) = &arguments.command {
+        undo(&arguments, &configuration, run_id.as_deref());
+        return;
+    }
+    recover(&arguments, &configuration);
+    if let Some(Command::Prune { remove_empty_directories }) = &arguments.command {
+        prune(&arguments, &configuration, *remove_empty_directories);
+        return;
+    }
+    if let Some(Command::Reconcile) = arguments.command {
+        reconcile(&arguments, &configuration);
+        return;
+    }
 
-    let mut remaining_nodes = run(&arguments, &configuration);
-    remaining_nodes.sort();
-    remaining_nodes.iter()
+    let mut report = match run(&arguments, &configuration) {
+        Ok(report) => report,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    report.remaining_nodes.sort();
+    report.remaining_nodes.iter()
         .for_each(|v| print(v.to_owned()));
+    if !report.broken_links.is_empty() {
+        warn!("Broken links:");
+        report.broken_links.iter()
+            .for_each(print_broken_link);
+    }
+    if !report.scan_errors.is_empty() {
+        error!("Scan errors:");
+        report.scan_errors.iter()
+            .for_each(|v| error!("{}", v));
+    }
+}
+
+fn check(arguments: &Arguments) {
+    let diagnostics = check_configuration_file(&arguments.configuration);
+    if diagnostics.is_empty() {
+        info!("Configuration {} is valid", arguments.configuration);
+        return;
+    }
+
+    diagnostics.iter()
+        .for_each(|v| error!("{}", v));
+    std::process::exit(1);
+}
+
+fn undo(arguments: &Arguments, configuration: &Configuration, run_id: Option<&str>) {
+    let result = configuration.journal.resolve_directory()
+        .map_err(|e| LinkerError::UnableToCreateJournal(e.to_string()))
+        .and_then(|v| undo_run(Path::new(&v), run_id, arguments.dry_run));
+    match result {
+        Ok(changes) => info!("Undid {} changes", changes),
+        Err(e) => {
+            error!("{}", e);
+            if e == LinkerError::NoRunToUndo && !configuration.journal.is_enabled() {
+                error!("Runs are only written to the journal when `journal` or `transactional` is configured");
+            }
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Transactional runs that were interrupted are rolled back before any changes are planned, so that
+/// the changes are planned from the targets as they were before the interrupted run. Without the
+/// journal there are no runs to recover.
+fn recover(arguments: &Arguments, configuration: &Configuration) {
+    if !configuration.journal.is_enabled() {
+        return;
+    }
+
+    let result = configuration.journal.resolve_directory()
+        .map_err(|e| LinkerError::UnableToCreateJournal(e.to_string()))
+        .and_then(|v| recover_interrupted_runs(Path::new(&v), arguments.dry_run));
+    match result {
+        Ok(0) => {}
+        Ok(runs) => info!("Rolled back {} interrupted runs", runs),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn prune(arguments: &Arguments, configuration: &Configuration, remove_empty_directories: bool) {
+    let source_nodes = require_complete_scan(collect_and_filter_source_nodes(configuration), "sources");
+    let target_nodes = require_complete_scan(
+        collect_target_nodes(configuration, &source_nodes.followed_links),
+        "targets",
+    );
+    let source_nodes = source_nodes.nodes;
+    let target_nodes = target_nodes.nodes;
+    let expected_links: Vec<Node> = find_expected_links(
+        &source_nodes,
+        &configuration.link_maps,
+        configuration.match_strategy,
+        &configuration.link_options,
+    )
+        .into_iter()
+        .map(|(link, _)| link)
+        .collect();
+    let links = find_prunable_links(&target_nodes, &expected_links, &configuration.sources);
+
+    let result = create_journal(arguments, configuration)
+        .and_then(|mut journal| {
+            let result = prune_links(
+                &links,
+                &configuration.targets,
+                remove_empty_directories,
+                &mut journal,
+                arguments.dry_run,
+            );
+            match result {
+                Ok(_) => journal.commit()?,
+                Err(_) => journal.rollback(),
+            }
+            result
+        });
+    match result {
+        Ok(entries) => info!("Pruned {} entries", entries),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reports the changes needed for the links within the targets to converge with the link maps,
+/// and applies them unless running with `--dry-run`.
+fn reconcile(arguments: &Arguments, configuration: &Configuration) {
+    let source_nodes = require_complete_scan(collect_and_filter_source_nodes(configuration), "sources");
+    let target_nodes = require_complete_scan(
+        collect_target_nodes(configuration, &source_nodes.followed_links),
+        "targets",
+    );
+    let target_nodes = target_nodes.nodes;
+    let desired_links = find_desired_links(&source_nodes.nodes, configuration);
+    let changes = plan_changes(&desired_links, &target_nodes, &configuration.sources);
+    info!("Reconciling {} changes", changes.len());
+    changes.iter()
+        .for_each(|v| info!("{}", v));
+    if arguments.dry_run || changes.is_empty() {
+        return;
+    }
+
+    let result = create_journal(arguments, configuration)
+        .and_then(|mut journal| {
+            let result = apply_changes(&changes, &source_nodes.followed_links, &mut journal);
+            match result {
+                Ok(_) => journal.commit()?,
+                Err(_) => journal.rollback(),
+            }
+            result
+        });
+    match result {
+        Ok(applied_changes) => info!("Applied {} of {} changes", applied_changes, changes.len()),
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Links that are missing from an incomplete scan of the sources would be removed, and links
+/// that are unable to be scanned within the targets could be mistaken for missing links, as such
+/// both have to be scanned without any errors.
+fn require_complete_scan(result: ScanResult, description: &str) -> ScanResult {
+    if !result.errors.is_empty() {
+        result.errors.iter()
+            .for_each(|v| error!("{}", v));
+        error!("Unable to scan the {}, no links were changed", description);
+        std::process::exit(1);
+    }
+    result
 }
 
-fn run(arguments: &Arguments, configuration: &Configuration) -> Vec<Node> {
+fn run(arguments: &Arguments, configuration: &Configuration) -> Result<Report, LinkerError> {
     let source_nodes = collect_and_filter_source_nodes(&configuration);
-    let target_nodes = collect_and_filter_target_nodes(&configuration);
-    let mut nodes = filter(&source_nodes, &target_nodes);
+    let mut target_nodes = collect_target_nodes(&configuration, &source_nodes.followed_links);
+    target_nodes.nodes = remove_leftover_temporary_links(
+        &target_nodes.nodes,
+        if arguments.dry_run {
+            remove_leftover_temporary_link_dry_run
+        } else {
+            remove_leftover_temporary_link
+        },
+    );
+    let mut nodes = filter_with_link_maps(
+        &source_nodes.nodes,
+        &filter_target_nodes(&target_nodes.nodes),
+        &configuration.link_maps,
+        configuration.match_strategy,
+    );
+    if uses_copies(configuration) {
+        nodes = filter_copied_nodes(
+            &nodes,
+            &configuration.link_maps,
+            configuration.match_strategy,
+            &configuration.link_options,
+        );
+    }
     nodes.sort();
 
-    link_nodes_matching_configuration(
+    let mut journal = create_journal(arguments, configuration)?;
+    let result = link_nodes_matching_configuration(
         &nodes,
-        &configuration.link_maps,
+        configuration,
+        &source_nodes.followed_links,
+        &mut journal,
         if arguments.dry_run {
             create_link_for_node_dry_run
         } else {
             create_link_for_node
         },
-    )
+    );
+    match result {
+        Ok(_) => journal.commit()?,
+        Err(_) => journal.rollback(),
+    }
+    result.map(|remaining_nodes| Report {
+        remaining_nodes,
+        broken_links: filter_broken_links(&target_nodes.nodes),
+        scan_errors: [source_nodes.errors, target_nodes.errors].concat(),
+    })
+}
+
+/// The journal is written for transactional runs, or when a journal directory is configured so
+/// that the run can be undone. A dry run doesn't change anything, i.e. there is nothing to write.
+fn create_journal(arguments: &Arguments, configuration: &Configuration) -> Result<Journal, LinkerError> {
+    let options = &configuration.journal;
+    if arguments.dry_run || !options.is_enabled() {
+        return Ok(Journal::disabled());
+    }
+
+    let directory = options.resolve_directory()
+        .map_err(|e| LinkerError::UnableToCreateJournal(e.to_string()))?;
+    Journal::create(Path::new(&directory), options.transactional)
+        .map_err(|e| LinkerError::UnableToCreateJournal(format!("{:?}: {}", directory, e)))
+}
+
+fn collect_and_filter_source_nodes(configuration: &Configuration) -> ScanResult {
+    if configuration.sources.is_empty() {
+        panic!("Unable to read path for sources from configuration");
+    }
+
+    let mut source_nodes: Vec<Node> = Vec::new();
+    let mut errors: Vec<ScanError> = Vec::new();
+    let mut followed_links: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for source in &configuration.sources {
+        let options = ScanOptions { max_depth: source.max_depth, follow_links: configuration.follow_source_links };
+        let result = collect_nodes_with_options(Path::new(&source.path), options);
+        source_nodes.extend(filter_source_nodes_with_followed_links(
+            &result.nodes,
+            &result.followed_links,
+            &configuration.excludes,
+        ));
+        errors.extend(result.errors);
+        followed_links.extend(result.followed_links);
+    }
+
+    if configuration.match_strategy == MatchStrategy::Error {
+        find_link_map_conflicts(&source_nodes, &configuration.link_maps).iter()
+            .for_each(|v| {
+                let link_maps = v.link_maps.iter()
+                    .map(|link_map| format!("{:?} -> {:?}", link_map.pattern(), link_map.target))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                error!("{:?} matches multiple link maps, it will not be linked: {}", v.path, link_maps)
+            });
+    }
+
+    let (nodes, shadowed_nodes) = shadow_source_nodes(
+        &source_nodes,
+        &configuration.link_maps,
+        configuration.match_strategy,
+    );
+    shadowed_nodes.iter()
+        .for_each(|v| warn!("{:?} is shadowed by {:?} when linking {:?}", v.path, v.shadowed_by, v.link));
+    ScanResult { nodes, errors, followed_links }
 }
 
-fn collect_and_filter_source_nodes(configuration: &Configuration) -> Vec<Node> {
-    let source_nodes = configuration.source.to_owned()
-        .map(|v| PathBuf::from(v.as_str()))
-        .map(|v| collect_nodes(&v))
-        .expect(&format!("Unable to read path for sources from configuration"));
+/// Temporary links left behind by interrupted runs are removed and left out from the target nodes,
+/// otherwise they could be mistaken for the link of a source.
+fn remove_leftover_temporary_links(nodes: &[Node], remove: fn(&Path)) -> Vec<Node> {
+    let leftover_links = find_leftover_temporary_links(nodes);
+    leftover_links.iter()
+        .for_each(|v| remove(v));
+    filter_nodes_by_path(nodes, &leftover_links)
+}
 
-    filter_source_nodes(&source_nodes, &configuration.excludes)
+fn filter_nodes_by_path(nodes: &[Node], paths: &[PathBuf]) -> Vec<Node> {
+    nodes.iter()
+        .filter_map(|v| match v {
+            Node::Branch(path, children) => Some(Node::Branch(path.to_owned(), filter_nodes_by_path(children, paths))),
+            Node::Link(path, _) | Node::BrokenLink(path, _) if paths.contains(path) => None,
+            _ => Some(v.to_owned()),
+        })
+        .collect()
 }
 
-fn collect_and_filter_target_nodes(configuration: &Configuration) -> Vec<Node> {
-    let target_nodes = &configuration.targets.to_owned()
-        .iter()
-        .map(|v| PathBuf::from(v.as_str()))
-        .flat_map(|v| collect_nodes(&v))
-        .collect::<Vec<Node>>();
+/// Links into the resolved path of a followed source link are collected with the path of the node
+/// within the sources, so that they can be compared with the source nodes.
+fn collect_target_nodes(configuration: &Configuration, followed_links: &[(PathBuf, PathBuf)]) -> ScanResult {
+    let mut target_nodes = ScanResult::default();
+    configuration.targets.iter()
+        .map(|v| {
+            let options = ScanOptions { max_depth: v.max_depth, ..ScanOptions::default() };
+            collect_nodes_with_options(Path::new(&v.path), options)
+        })
+        .for_each(|v| target_nodes.extend(v));
+    if !followed_links.is_empty() {
+        target_nodes.nodes = map_followed_link_sources(&target_nodes.nodes, followed_links, &configuration.sources);
+    }
+    target_nodes
+}
 
-    filter_target_nodes(&target_nodes)
+/// Hard links, reflinks and copies can only be recognised by comparing the leaves within the
+/// targets, which is only done when a link map is using any of them.
+fn uses_copies(configuration: &Configuration) -> bool {
+    configuration.link_maps.iter()
+        .flat_map(|v| v.link_options(&configuration.link_options).modes)
+        .any(|v| v != LinkMode::Symlink)
 }
 
-fn link_nodes_matching_configuration(nodes: &[Node], link_maps: &[LinkMap], create_link: fn(&Node) -> bool) -> Vec<Node> {
+fn link_nodes_matching_configuration(
+    nodes: &[Node],
+    configuration: &Configuration,
+    followed_links: &[(PathBuf, PathBuf)],
+    journal: &mut Journal,
+    create_link: CreateLink,
+) -> Result<Vec<Node>, LinkerError> {
     nodes.iter()
-        .flat_map(|v| link_node_matching_configuration(&v, &link_maps, create_link))
-        .collect::<Vec<Node>>()
+        .map(|v| link_node_matching_configuration(v, configuration, followed_links, journal, create_link))
+        .collect::<Result<Vec<Vec<Node>>, LinkerError>>()
+        .map(|v| v.concat())
 }
 
+/// Links to a followed link within the sources are created to its resolved path.
 fn link_node_matching_configuration(
     nodes: &Node,
-    link_maps: &&[LinkMap],
-    create_link: fn(&Node) -> bool,
-) -> Vec<Node> {
-    match match_link_maps(&nodes, &link_maps) {
-        Some(n) => create_node_link(&n, create_link),
-        None => match nodes {
-            Node::Leaf(_) => vec![nodes.to_owned()],
-            Node::Link(_, _) => vec![nodes.to_owned()],
-            Node::Branch(path, nodes) => {
-                let remaining_nodes = link_nodes_matching_configuration(&nodes, link_maps, create_link);
-                if !remaining_nodes.is_empty() {
-                    vec![Node::Branch(path.to_owned(), remaining_nodes)]
-                } else {
-                    vec![]
-                }
+    configuration: &Configuration,
+    followed_links: &[(PathBuf, PathBuf)],
+    journal: &mut Journal,
+    create_link: CreateLink,
+) -> Result<Vec<Node>, LinkerError> {
+    let links = match_link_maps_with_options(
+        nodes,
+        &configuration.link_maps,
+        configuration.match_strategy,
+        &configuration.link_options,
+    );
+    if !links.is_empty() {
+        return links.into_iter()
+            .map(|(n, options)| {
+                let options = link_options_for_target(configuration, &n, options);
+                create_node_link(&resolve_followed_link_source(&n, followed_links), &options, journal, create_link)
+            })
+            .collect::<Result<Vec<Vec<Node>>, LinkerError>>()
+            .map(|v| v.concat());
+    }
+
+    match nodes {
+        Node::Leaf(_) => Ok(vec![nodes.to_owned()]),
+        Node::Link(_, _) => Ok(vec![nodes.to_owned()]),
+        Node::BrokenLink(_, _) => Ok(vec![nodes.to_owned()]),
+        Node::Branch(path, nodes) => {
+            let remaining_nodes =
+                link_nodes_matching_configuration(nodes, configuration, followed_links, journal, create_link)?;
+            if !remaining_nodes.is_empty() {
+                Ok(vec![Node::Branch(path.to_owned(), remaining_nodes)])
+            } else {
+                Ok(vec![])
             }
         }
     }
 }
 
-fn create_node_link(node: &Node, create_link: fn(&Node) -> bool) -> Vec<Node> {
-    if create_link(&node) {
-        vec![]
-    } else {
-        vec![node.to_owned()]
+/// Finds the links that the link maps would produce for the nodes together with the options used
+/// when creating them within their target.
+fn find_desired_links(nodes: &[Node], configuration: &Configuration) -> Vec<(Node, LinkOptions)> {
+    find_expected_links(nodes, &configuration.link_maps, configuration.match_strategy, &configuration.link_options)
+        .into_iter()
+        .map(|(n, options)| {
+            let options = link_options_for_target(configuration, &n, options);
+            (n, options)
+        })
+        .collect()
+}
+
+fn link_options_for_target(configuration: &Configuration, node: &Node, options: LinkOptions) -> LinkOptions {
+    let target = target_for_link(configuration, node);
+    LinkOptions {
+        directory: target.map(|v| v.directory).unwrap_or_default(),
+        on_conflict: target.map(|v| v.on_conflict).unwrap_or_default(),
+        ..options
     }
 }
 
+/// Directories created for the link and conflicts at the link path are handled using the options
+/// from the target containing the link, with nested targets the innermost target is used.
+fn target_for_link<'a>(configuration: &'a Configuration, node: &Node) -> Option<&'a Target> {
+    match node {
+        Node::Link(link, _) => configuration.targets.iter()
+            .filter(|v| Path::new(link).starts_with(&v.path))
+            .max_by_key(|v| v.path.len()),
+        _ => None,
+    }
+}
+
+fn create_node_link(
+    node: &Node,
+    options: &LinkOptions,
+    journal: &mut Journal,
+    create_link: CreateLink,
+) -> Result<Vec<Node>, LinkerError> {
+    create_link(node, options, journal)
+        .map(|linked| if linked { vec![] } else { vec![node.to_owned()] })
+}
+
 fn print(node: Node) {
     match node {
         Node::Leaf(path) => info!("{:?}", path),
         Node::Link(target, _) => info!("{:?}", target),
+        Node::BrokenLink(target, _) => info!("{:?}", target),
         Node::Branch(path, mut nodes) => {
             info!("{:?}", path);
             nodes.sort();
@@ -138,6 +533,12 @@ fn print(node: Node) {
     }
 }
 
+fn print_broken_link(node: &Node) {
+    if let Node::BrokenLink(path, source) = node {
+        warn!("{:?} -> {:?}", path, source);
+    }
+}
+
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
@@ -149,9 +550,12 @@ mod tests {
 
     use crate::arguments::Arguments;
     use crate::collect_nodes::collect_nodes;
-    use crate::configuration::{Configuration, LinkMap};
+    use crate::configuration::{
+        ConflictPolicy, Configuration, JournalOptions, LinkMap, LinkMode, LinkOptions, MatchStrategy, Source, Target,
+    };
+    use crate::linker_error::LinkerError;
     use crate::node::Node;
-    use crate::run;
+    use crate::{Report, run};
 
     fn create_temporary_directory() -> TempDir {
         TempDir::new()
@@ -188,10 +592,13 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let configuration = Configuration::default();
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
     }
 
     #[test]
@@ -200,15 +607,22 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let configuration = Configuration {
-            source: None,
+            sources: vec![],
             targets: vec![],
             excludes: vec![],
             link_maps: vec![],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
     }
 
     // Run
@@ -220,6 +634,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -227,9 +643,11 @@ mod tests {
         create_file(&sources_path.join("name.pkg.tar.zst"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -238,17 +656,22 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![
             Node::Link(
-                as_string(&targets_path.join("name.pkg.tar.zst")),
-                as_string(&sources_path.join("name.pkg.tar.zst")),
+                targets_path.join("name.pkg.tar.zst"),
+                sources_path.join("name.pkg.tar.zst"),
             ),
         ];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -259,6 +682,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -266,9 +691,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -277,17 +704,22 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![
             Node::Link(
-                as_string(&targets_path.join("folder")),
-                as_string(&sources_path.join("folder")),
+                targets_path.join("folder"),
+                sources_path.join("folder"),
             ),
         ];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -298,6 +730,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -306,9 +740,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder").join("subfolder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -317,17 +753,22 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![
             Node::Link(
-                as_string(&targets_path.join("subfolder")),
-                as_string(&sources_path.join("folder").join("subfolder")),
+                targets_path.join("subfolder"),
+                sources_path.join("folder").join("subfolder"),
             ),
         ];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -338,6 +779,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: false,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -346,9 +789,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder").join("folder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -357,18 +802,242 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                targets_path.join("folder"),
+                sources_path.join("folder"),
+            ),
+        ];
+
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+
+        let actual = collect_nodes(&targets_path).nodes;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn run_with_duplicate_leaf_in_multiple_sources() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let build_path = path.join("build");
+        let cache_path = path.join("cache");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&build_path);
+        create_file(&build_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&cache_path);
+        create_file(&cache_path.join("name.pkg.tar.zst"));
+        create_file(&cache_path.join("other.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&build_path), 10),
+                Source::new(as_string(&cache_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap()
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                targets_path.join("name.pkg.tar.zst"),
+                build_path.join("name.pkg.tar.zst"),
+            ),
+            Node::Link(
+                targets_path.join("other.pkg.tar.zst"),
+                cache_path.join("other.pkg.tar.zst"),
+            ),
+        ];
+
+        let remaining_nodes = run(&arguments, &configuration)
+            .expect("Unable to link nodes")
+            .remaining_nodes;
+
+        let actual = collect_nodes(&targets_path).nodes;
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), remaining_nodes);
+    }
+
+    #[test]
+    fn run_with_multiple_matching_link_maps_using_all() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let packages_path = path.join("packages");
+        let latest_path = path.join("latest");
+        create_directory_at_path(&sources_path);
+        create_file(&sources_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&packages_path);
+        create_directory_at_path(&latest_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&packages_path)),
+                Target::new(as_string(&latest_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&packages_path),
+                ).unwrap(),
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&latest_path),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::All,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![
             Node::Link(
-                as_string(&targets_path.join("folder")),
-                as_string(&sources_path.join("folder")),
+                packages_path.join("name.pkg.tar.zst"),
+                sources_path.join("name.pkg.tar.zst"),
+            ),
+            Node::Link(
+                latest_path.join("name.pkg.tar.zst"),
+                sources_path.join("name.pkg.tar.zst"),
             ),
         ];
 
-        run(&arguments, &configuration);
+        let remaining_nodes = run(&arguments, &configuration)
+            .expect("Unable to link nodes")
+            .remaining_nodes;
+
+        let actual = [collect_nodes(&packages_path).nodes, collect_nodes(&latest_path).nodes].concat();
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), remaining_nodes);
+    }
+
+    #[test]
+    fn run_with_multiple_matching_link_maps_using_error() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        create_file(&sources_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap(),
+                LinkMap::new(
+                    "name".to_string(),
+                    as_string(&targets_path),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Error,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Leaf(sources_path.join("name.pkg.tar.zst")),
+        ];
+
+        let actual = run(&arguments, &configuration)
+            .expect("Unable to link nodes")
+            .remaining_nodes;
+
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), collect_nodes(&targets_path).nodes);
+    }
+
+    #[test]
+    fn run_with_hard_link_mode_when_already_linked() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        create_file(&sources_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap().with_modes(vec![LinkMode::Hardlink])
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Leaf(targets_path.join("name.pkg.tar.zst")),
+        ];
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+
+        let remaining_nodes = run(&arguments, &configuration)
+            .expect("Unable to link nodes")
+            .remaining_nodes;
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), remaining_nodes);
     }
 
     // Dry run
@@ -380,6 +1049,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: true,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -387,9 +1058,11 @@ mod tests {
         create_file(&sources_path.join("name.pkg.tar.zst"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -398,12 +1071,17 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -414,6 +1092,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: true,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -421,9 +1101,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -432,12 +1114,17 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -448,6 +1135,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: true,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -456,9 +1145,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder").join("subfolder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -467,12 +1158,17 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![];
 
-        run(&arguments, &configuration);
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
         assert_eq!(expected, actual);
     }
 
@@ -483,6 +1179,8 @@ mod tests {
         let arguments = Arguments {
             configuration: "/etc/linker/configuration.json".to_string(),
             dry_run: true,
+            jobs: 0,
+            command: None,
         };
         let sources_path = path.join("sources");
         let targets_path = path.join("targets");
@@ -491,9 +1189,11 @@ mod tests {
         create_directory_at_path(&sources_path.join("folder").join("folder"));
         create_directory_at_path(&targets_path);
         let configuration = Configuration {
-            source: Some(as_string(&sources_path)),
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
             targets: vec![
-                as_string(&targets_path),
+                Target::new(as_string(&targets_path)),
             ],
             excludes: vec![],
             link_maps: vec![
@@ -502,12 +1202,289 @@ mod tests {
                     as_string(&targets_path),
                 ).unwrap()
             ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![];
+
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+
+        let actual = collect_nodes(&targets_path).nodes;
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn run_with_conflict_using_fail() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        create_file(&sources_path.join("name-1.pkg.tar.zst"));
+        create_file(&sources_path.join("name-2.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        create_file(&targets_path.join("name-1.pkg.tar.zst"));
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path))
+                    .with_on_conflict(ConflictPolicy::Fail),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected = Err(LinkerError::LinkPathAlreadyExists(targets_path.join("name-1.pkg.tar.zst")));
+
+        let actual = run(&arguments, &configuration);
+
+        assert_eq!(expected, actual);
+        assert!(fs::symlink_metadata(targets_path.join("name-2.pkg.tar.zst")).is_err())
+    }
+
+    #[test]
+    fn run_with_followed_source_links() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let packages_path = path.join("packages");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        create_directory_at_path(&packages_path);
+        create_file(&packages_path.join("name.pkg.tar.zst"));
+        std::os::unix::fs::symlink("../packages", sources_path.join("latest"))
+            .expect("Unable to create symbolic link");
+        create_directory_at_path(&targets_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap()
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: true,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                targets_path.join("name.pkg.tar.zst"),
+                packages_path.join("name.pkg.tar.zst"),
+            ),
+        ];
+
+        run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+        let report = run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+
+        let actual = collect_nodes(&targets_path).nodes;
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), report.remaining_nodes);
+    }
+
+    #[test]
+    fn run_with_broken_link_in_target() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        let source = create_file(&sources_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let broken_link_path = targets_path.join("removed.pkg.tar.zst");
+        std::os::unix::fs::symlink("../sources/removed.pkg.tar.zst", &broken_link_path)
+            .expect("Unable to create symbolic link");
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected = Report {
+            remaining_nodes: vec![],
+            broken_links: vec![
+                Node::BrokenLink(broken_link_path.clone(), PathBuf::from("../sources/removed.pkg.tar.zst")),
+            ],
+            scan_errors: vec![],
+        };
+
+        let actual = run(&arguments, &configuration)
+            .expect("Unable to link nodes");
+
+        assert_eq!(expected, actual);
+        assert_eq!(
+            vec![
+                Node::Link(targets_path.join("name.pkg.tar.zst"), PathBuf::from(source)),
+                Node::BrokenLink(broken_link_path, PathBuf::from("../sources/removed.pkg.tar.zst")),
+            ],
+            collect_nodes(&targets_path).nodes
+        );
+    }
+
+    #[test]
+    fn run_with_leftover_temporary_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        create_directory_at_path(&sources_path);
+        let source = create_file(&sources_path.join("name.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let mut child = std::process::Command::new("true")
+            .spawn()
+            .expect("Unable to spawn process");
+        child.wait()
+            .expect("Unable to wait for process");
+        let temporary_path = targets_path.join(format!(".name.pkg.tar.zst.linker-tmp.{}", child.id()));
+        std::os::unix::fs::symlink(&source, &temporary_path)
+            .expect("Unable to create symbolic link");
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions::default(),
+            journal: JournalOptions::default(),
+            follow_source_links: false,
+        };
+        let expected: Vec<Node> = vec![
+            Node::Link(targets_path.join("name.pkg.tar.zst"), PathBuf::from(source)),
+        ];
+
+        let remaining_nodes = run(&arguments, &configuration)
+            .expect("Unable to link nodes")
+            .remaining_nodes;
+
+        let actual = collect_nodes(&targets_path).nodes;
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), remaining_nodes);
+    }
+
+    #[test]
+    fn run_with_failed_link_using_transactional() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let arguments = Arguments {
+            configuration: "/etc/linker/configuration.json".to_string(),
+            dry_run: false,
+            jobs: 0,
+            command: None,
+        };
+        let sources_path = path.join("sources");
+        let targets_path = path.join("targets");
+        let journal_path = path.join("journal");
+        create_directory_at_path(&sources_path);
+        create_file(&sources_path.join("name-1.pkg.tar.zst"));
+        create_directory_at_path(&sources_path.join("name-2.pkg.tar.zst"));
+        create_directory_at_path(&targets_path);
+        let configuration = Configuration {
+            sources: vec![
+                Source::new(as_string(&sources_path), 0),
+            ],
+            targets: vec![
+                Target::new(as_string(&targets_path)),
+            ],
+            excludes: vec![],
+            link_maps: vec![
+                LinkMap::new(
+                    "(.*)\\.pkg\\.tar\\.zst".to_string(),
+                    as_string(&targets_path.join("packages")),
+                ).unwrap(),
+            ],
+            match_strategy: MatchStrategy::Last,
+            link_options: LinkOptions {
+                modes: vec![LinkMode::Hardlink],
+                ..Default::default()
+            },
+            journal: JournalOptions {
+                transactional: true,
+                directory: Some(as_string(&journal_path)),
+            },
+            follow_source_links: false,
         };
         let expected: Vec<Node> = vec![];
 
-        run(&arguments, &configuration);
+        let result = run(&arguments, &configuration);
 
-        let actual = collect_nodes(&targets_path);
+        let actual = collect_nodes(&targets_path).nodes;
+        assert!(result.is_err());
         assert_eq!(expected, actual);
+        let journal = fs::read_dir(&journal_path)
+            .expect("Unable to read journal directory")
+            .filter_map(|v| v.ok())
+            .find(|v| v.file_name().to_string_lossy().ends_with(".journal"))
+            .map(|v| fs::read_to_string(v.path()).expect("Unable to read journal"))
+            .expect("Unable to find journal");
+        assert_eq!(Some(r#"{"status":"rolledBack"}"#), journal.lines().last())
     }
 }
+
@@ -15,43 +15,123 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::path::MAIN_SEPARATOR;
+use std::path::{Path, PathBuf};
 
-use crate::configuration::LinkMap;
+use crate::configuration::{LinkMap, LinkOptions, MatchStrategy};
 use crate::node::Node;
 
-pub fn match_link_maps(node: &Node, link_maps: &[LinkMap]) -> Option<Node> {
-    return match node {
-        Node::Leaf(path) => find_link_map_match(path, link_maps),
-        Node::Link(_, _) => None,
-        Node::Branch(path, _) => find_link_map_match(path, link_maps),
-    };
+/// Node that matches more than one link map while using the `error` match strategy.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct LinkMapConflict {
+    pub path: PathBuf,
+    pub link_maps: Vec<LinkMap>,
+}
+
+pub fn match_link_maps(node: &Node, link_maps: &[LinkMap], match_strategy: MatchStrategy) -> Vec<Node> {
+    find_link_map_matches(node, link_maps, match_strategy).into_iter()
+        .map(|(link, _)| link)
+        .collect()
 }
 
-fn find_link_map_match(path: &String, link_maps: &[LinkMap]) -> Option<Node> {
-    if path.is_empty() {
-        return None;
+/// Same as `match_link_maps`, but with the options used when creating each of the links.
+pub fn match_link_maps_with_options(
+    node: &Node,
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    link_options: &LinkOptions,
+) -> Vec<(Node, LinkOptions)> {
+    find_link_map_matches(node, link_maps, match_strategy).into_iter()
+        .map(|(link, link_map)| (link, link_map.link_options(link_options)))
+        .collect()
+}
+
+fn find_link_map_matches<'a>(
+    node: &Node,
+    link_maps: &'a [LinkMap],
+    match_strategy: MatchStrategy,
+) -> Vec<(Node, &'a LinkMap)> {
+    match node {
+        Node::Leaf(path) => find_link_map_match(path, link_maps, match_strategy),
+        Node::Link(_, _) => Vec::new(),
+        Node::BrokenLink(_, _) => Vec::new(),
+        Node::Branch(path, _) => find_link_map_match(path, link_maps, match_strategy),
     }
+}
+
+/// Finds the nodes that match more than one link map, branches that match a single link map are
+/// linked as a whole and as such their children are not checked.
+pub fn find_link_map_conflicts(nodes: &[Node], link_maps: &[LinkMap]) -> Vec<LinkMapConflict> {
+    nodes.iter()
+        .flat_map(|node| {
+            let path = match node {
+                Node::Leaf(path) => path,
+                Node::Link(_, _) => return Vec::new(),
+                Node::BrokenLink(_, _) => return Vec::new(),
+                Node::Branch(path, _) => path,
+            };
+
+            let matching_link_maps = find_matching_link_maps(path, link_maps);
+            match (matching_link_maps.len(), node) {
+                (0, Node::Branch(_, children)) => find_link_map_conflicts(children, link_maps),
+                (0, _) | (1, _) => Vec::new(),
+                _ => vec![
+                    LinkMapConflict {
+                        path: path.to_path_buf(),
+                        link_maps: matching_link_maps.into_iter().cloned().collect(),
+                    }
+                ],
+            }
+        })
+        .collect()
+}
+
+fn find_link_map_match<'a>(
+    path: &Path,
+    link_maps: &'a [LinkMap],
+    match_strategy: MatchStrategy,
+) -> Vec<(Node, &'a LinkMap)> {
+    let matching_link_maps = find_matching_link_maps(path, link_maps);
+    let selected_link_maps = match match_strategy {
+        MatchStrategy::First => matching_link_maps.into_iter().take(1).collect(),
+        MatchStrategy::Last => matching_link_maps.into_iter().last().into_iter().collect(),
+        MatchStrategy::All => matching_link_maps,
+        MatchStrategy::Error if matching_link_maps.len() > 1 => Vec::new(),
+        MatchStrategy::Error => matching_link_maps,
+    };
 
-    path.rsplit(MAIN_SEPARATOR).next()
-        .and_then(|basename| {
+    path.file_name()
+        .map(|basename| {
+            selected_link_maps.into_iter()
+                .filter_map(|link_map| {
+                    let link = Node::Link(
+                        Path::new(&link_map.target).join(link_map.link_name(basename)?),
+                        path.to_path_buf(),
+                    );
+                    Some((link, link_map))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn find_matching_link_maps<'a>(path: &Path, link_maps: &'a [LinkMap]) -> Vec<&'a LinkMap> {
+    path.file_name()
+        .map(|basename| {
             link_maps.iter()
                 .filter(|v| v.is_match(basename))
-                .last()
-                .map(|link_map| {
-                    Node::Link(
-                        [link_map.target.to_string(), basename.to_string()]
-                            .join(&MAIN_SEPARATOR.to_string()),
-                        path.to_string(),
-                    )
-                })
+                .collect()
         })
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::configuration::LinkMap;
-    use crate::match_link_maps::match_link_maps;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::PathBuf;
+
+    use crate::configuration::{LinkMap, MatchStrategy};
+    use crate::match_link_maps::{find_link_map_conflicts, LinkMapConflict, match_link_maps};
     use crate::node::Node;
 
 // Leaf
@@ -59,12 +139,12 @@ mod tests {
     #[test]
     fn match_leaf_with_empty_path() {
         let node: Node = Node::Leaf(
-            "".to_string()
+            PathBuf::from("")
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -72,12 +152,12 @@ mod tests {
     #[test]
     fn match_leaf_without_link_maps() {
         let node: Node = Node::Leaf(
-            "/var/tmp/sources/leaf".to_string()
+            PathBuf::from("/var/tmp/sources/leaf")
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -85,7 +165,7 @@ mod tests {
     #[test]
     fn match_leaf_without_matching_link_map() {
         let node: Node = Node::Leaf(
-            "/var/tmp/sources/leaf".to_string()
+            PathBuf::from("/var/tmp/sources/leaf")
         );
         let link_maps: Vec<LinkMap> = vec![
             LinkMap::new(
@@ -93,9 +173,9 @@ mod tests {
                 "/var/tmp/targets".to_string(),
             ).unwrap()
         ];
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -103,7 +183,7 @@ mod tests {
     #[test]
     fn match_leaf_with_matching_link_map() {
         let node: Node = Node::Leaf(
-            "/var/tmp/sources/leaf".to_string()
+            PathBuf::from("/var/tmp/sources/leaf")
         );
         let link_maps: Vec<LinkMap> = vec![
             LinkMap::new(
@@ -111,14 +191,14 @@ mod tests {
                 "/var/tmp/targets".to_string(),
             ).unwrap()
         ];
-        let expected: Option<Node> = Some(
+        let expected: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/targets/leaf".to_string(),
-                "/var/tmp/sources/leaf".to_string(),
+                PathBuf::from("/var/tmp/targets/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
             )
-        );
+        ];
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -126,7 +206,7 @@ mod tests {
     #[test]
     fn match_leaf_with_matching_link_map_using_regex() {
         let node: Node = Node::Leaf(
-            "/var/tmp/sources/leaf-1".to_string()
+            PathBuf::from("/var/tmp/sources/leaf-1")
         );
         let link_maps: Vec<LinkMap> = vec![
             LinkMap::new(
@@ -134,14 +214,220 @@ mod tests {
                 "/var/tmp/targets".to_string(),
             ).unwrap()
         ];
-        let expected: Option<Node> = Some(
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/leaf-1"),
+                PathBuf::from("/var/tmp/sources/leaf-1"),
+            )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_matching_link_map_using_name() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/linker-1.0.0-1-x86_64.pkg.tar.zst")
+        );
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "(.*)-x86_64\\.pkg\\.tar\\.zst".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap().with_name("$1.pkg.tar.zst".to_string())
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linker-1.0.0-1.pkg.tar.zst"),
+                PathBuf::from("/var/tmp/sources/linker-1.0.0-1-x86_64.pkg.tar.zst"),
+            )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_matching_link_map_using_named_capture() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/linker-1.0.0.tar.gz")
+        );
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "(?P<name>[a-z]+)-[0-9.]+\\.tar\\.gz".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap().with_name("${name}-latest".to_string())
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets/linker-latest"),
+                PathBuf::from("/var/tmp/sources/linker-1.0.0.tar.gz"),
+            )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_non_utf8_basename() {
+        let node: Node = Node::Leaf(
+            PathBuf::from(OsStr::from_bytes(b"/var/tmp/sources/caf\xe9.flac"))
+        );
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "(?-u:caf.)\\.flac".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap()
+        ];
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from(OsStr::from_bytes(b"/var/tmp/targets/caf\xe9.flac")),
+                PathBuf::from(OsStr::from_bytes(b"/var/tmp/sources/caf\xe9.flac")),
+            )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_matching_link_map_using_empty_name() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/leaf")
+        );
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap().with_name("$1".to_string())
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_matching_link_map_using_parent_name() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/..leaf")
+        );
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "^(.*)leaf$".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap().with_name("$1".to_string())
+        ];
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    fn link_maps_matching_leaf() -> Vec<LinkMap> {
+        vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets-1".to_string(),
+            ).unwrap(),
+            LinkMap::new(
+                "regex".to_string(),
+                "/var/tmp/targets-2".to_string(),
+            ).unwrap(),
+            LinkMap::new(
+                "^leaf$".to_string(),
+                "/var/tmp/targets-3".to_string(),
+            ).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn match_leaf_with_multiple_matching_link_maps_using_first() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/leaf")
+        );
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-1/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps_matching_leaf(), MatchStrategy::First);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_multiple_matching_link_maps_using_last() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/leaf")
+        );
+        let expected: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/targets/leaf-1".to_string(),
-                "/var/tmp/sources/leaf-1".to_string(),
+                PathBuf::from("/var/tmp/targets-3/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
             )
+        ];
+
+        let actual = match_link_maps(&node, &link_maps_matching_leaf(), MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_multiple_matching_link_maps_using_all() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/leaf")
         );
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-1/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-3/leaf"),
+                PathBuf::from("/var/tmp/sources/leaf"),
+            ),
+        ];
+
+        let actual = match_link_maps(&node, &link_maps_matching_leaf(), MatchStrategy::All);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_multiple_matching_link_maps_using_error() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/leaf")
+        );
+        let expected: Vec<Node> = Vec::new();
+
+        let actual = match_link_maps(&node, &link_maps_matching_leaf(), MatchStrategy::Error);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn match_leaf_with_single_matching_link_map_using_error() {
+        let node: Node = Node::Leaf(
+            PathBuf::from("/var/tmp/sources/regex")
+        );
+        let expected: Vec<Node> = vec![
+            Node::Link(
+                PathBuf::from("/var/tmp/targets-2/regex"),
+                PathBuf::from("/var/tmp/sources/regex"),
+            )
+        ];
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps_matching_leaf(), MatchStrategy::Error);
 
         assert_eq!(expected, actual)
     }
@@ -151,13 +437,13 @@ mod tests {
     #[test]
     fn match_link_with_empty_path() {
         let node: Node = Node::Link(
-            "".to_string(),
-            "".to_string(),
+            PathBuf::from(""),
+            PathBuf::from(""),
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -165,13 +451,13 @@ mod tests {
     #[test]
     fn match_link_without_link_maps() {
         let node: Node = Node::Link(
-            "/var/tmp/sources/link".to_string(),
-            "/var/tmp/sources/leaf".to_string(),
+            PathBuf::from("/var/tmp/sources/link"),
+            PathBuf::from("/var/tmp/sources/leaf"),
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -181,13 +467,13 @@ mod tests {
     #[test]
     fn match_branch_with_empty_path() {
         let node: Node = Node::Branch(
-            "".to_string(),
+            PathBuf::from(""),
             Vec::new(),
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -195,13 +481,13 @@ mod tests {
     #[test]
     fn match_branch_without_link_maps() {
         let node: Node = Node::Branch(
-            "/var/tmp/sources/branch".to_string(),
+            PathBuf::from("/var/tmp/sources/branch"),
             Vec::new(),
         );
         let link_maps: Vec<LinkMap> = Vec::new();
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -209,7 +495,7 @@ mod tests {
     #[test]
     fn match_branch_without_matching_link_map() {
         let node: Node = Node::Branch(
-            "/var/tmp/sources/branch".to_string(),
+            PathBuf::from("/var/tmp/sources/branch"),
             Vec::new(),
         );
         let link_maps: Vec<LinkMap> = vec![
@@ -218,9 +504,9 @@ mod tests {
                 "/var/tmp/targets".to_string(),
             ).unwrap()
         ];
-        let expected: Option<Node> = None;
+        let expected: Vec<Node> = Vec::new();
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
 
         assert_eq!(expected, actual)
     }
@@ -228,7 +514,7 @@ mod tests {
     #[test]
     fn match_branch_with_matching_link_map() {
         let node: Node = Node::Branch(
-            "/var/tmp/sources/branch".to_string(),
+            PathBuf::from("/var/tmp/sources/branch"),
             Vec::new(),
         );
         let link_maps: Vec<LinkMap> = vec![
@@ -237,14 +523,57 @@ mod tests {
                 "/var/tmp/targets".to_string(),
             ).unwrap()
         ];
-        let expected: Option<Node> = Some(
+        let expected: Vec<Node> = vec![
             Node::Link(
-                "/var/tmp/targets/branch".to_string(),
-                "/var/tmp/sources/branch".to_string(),
+                PathBuf::from("/var/tmp/targets/branch"),
+                PathBuf::from("/var/tmp/sources/branch"),
             )
-        );
+        ];
+
+        let actual = match_link_maps(&node, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    // Conflicts
+
+    #[test]
+    fn find_link_map_conflicts_without_conflicts() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/sources/regex")),
+            Node::Leaf(PathBuf::from("/var/tmp/sources/leaf-1")),
+        ];
+        let expected: Vec<LinkMapConflict> = Vec::new();
+
+        let actual = find_link_map_conflicts(&nodes, &link_maps_matching_leaf());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn find_link_map_conflicts_with_conflict_in_branch() {
+        let nodes: Vec<Node> = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/sources/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/sources/branch/leaf")),
+                    Node::Leaf(PathBuf::from("/var/tmp/sources/branch/leaf-1")),
+                ],
+            ),
+            Node::Link(
+                PathBuf::from("/var/tmp/sources/leaf"),
+                PathBuf::from("/var/tmp/leaf"),
+            ),
+        ];
+        let link_maps = link_maps_matching_leaf();
+        let expected: Vec<LinkMapConflict> = vec![
+            LinkMapConflict {
+                path: PathBuf::from("/var/tmp/sources/branch/leaf"),
+                link_maps: vec![link_maps[0].clone(), link_maps[2].clone()],
+            },
+        ];
 
-        let actual = match_link_maps(&node, &link_maps);
+        let actual = find_link_map_conflicts(&nodes, &link_maps);
 
         assert_eq!(expected, actual)
     }
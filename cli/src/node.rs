@@ -15,9 +15,13 @@
  * along with this program.  If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::path::PathBuf;
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Debug)]
 pub enum Node {
-    Leaf(String),
-    Link(String, String),
-    Branch(String, Vec<Node>),
+    Leaf(PathBuf),
+    Link(PathBuf, PathBuf),
+    /// Link whose source can't be resolved, with the path and the raw source of the link.
+    BrokenLink(PathBuf, PathBuf),
+    Branch(PathBuf, Vec<Node>),
 }
@@ -0,0 +1,393 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use log::{info, warn};
+
+use crate::configuration::{LinkMap, LinkOptions, MatchStrategy, Source, Target};
+use crate::journal::Journal;
+use crate::link::is_suffixed_link_path;
+use crate::linker_error::LinkerError;
+use crate::match_link_maps::match_link_maps_with_options;
+use crate::node::Node;
+
+/// Finds the links that the link maps would produce for the source nodes together with the options
+/// used when creating them, branches without a matching link map are linked by their children.
+pub(crate) fn find_expected_links(
+    nodes: &[Node],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    link_options: &LinkOptions,
+) -> Vec<(Node, LinkOptions)> {
+    nodes.iter()
+        .flat_map(|node| {
+            let links = match_link_maps_with_options(node, link_maps, match_strategy, link_options);
+            match node {
+                Node::Branch(_, children) if links.is_empty() => {
+                    find_expected_links(children, link_maps, match_strategy, link_options)
+                }
+                _ => links,
+            }
+        })
+        .collect()
+}
+
+/// Finds the links within the targets that should be pruned, i.e. broken links into the sources
+/// and links into the sources that none of the link maps would produce. Links outside of the
+/// sources are never pruned.
+pub(crate) fn find_prunable_links(target_nodes: &[Node], expected_links: &[Node], sources: &[Source]) -> Vec<Node> {
+    target_nodes.iter()
+        .flat_map(|node| match node {
+            Node::Branch(_, children) => find_prunable_links(children, expected_links, sources),
+            Node::BrokenLink(path, source) if is_within_sources(&resolve_broken_link_source(path, source), sources) => {
+                vec![node.to_owned()]
+            }
+            Node::Link(path, source) if is_orphaned(path, source, expected_links, sources) => {
+                vec![node.to_owned()]
+            }
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// A link created next to the expected link path by the `suffix` conflict policy, e.g. `name~1`,
+/// is expected as well.
+fn is_orphaned(path: &Path, source: &Path, expected_links: &[Node], sources: &[Source]) -> bool {
+    let is_within_sources = is_within_sources(source, sources);
+    let is_expected = expected_links.iter()
+        .any(|v| match v {
+            Node::Link(expected_path, expected_source) => {
+                (expected_path == path || is_suffixed_link_path(path, expected_path)) && expected_source == source
+            }
+            _ => false,
+        });
+
+    is_within_sources && !is_expected
+}
+
+/// Resolves the source of the broken link relative to the directory of the link. As the source
+/// doesn't exist it can't be canonicalized, instead `.` and `..` are resolved lexically.
+fn resolve_broken_link_source(path: &Path, source: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    let parent = path.parent().unwrap_or(Path::new("/"));
+    for component in parent.join(source).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => resolved.push(component),
+        }
+    }
+    resolved
+}
+
+pub(crate) fn is_within_sources(path: &Path, sources: &[Source]) -> bool {
+    sources.iter()
+        .any(|v| path.starts_with(&v.path))
+}
+
+/// Removes the links and, when enabled, the directories within the targets that are left empty.
+/// Failing to remove an entry is reported and the entry is left, unless the run is transactional
+/// in which case the error is returned so that the run can be rolled back. Returns the number of
+/// removed entries.
+pub(crate) fn prune_links(
+    links: &[Node],
+    targets: &[Target],
+    remove_empty_directories: bool,
+    journal: &mut Journal,
+    dry_run: bool,
+) -> Result<usize, LinkerError> {
+    let mut removed_paths: HashSet<PathBuf> = HashSet::new();
+    for link in links {
+        let (path, description) = match link {
+            Node::BrokenLink(path, source) => {
+                (path, format!("broken link {} -> {}", path.display(), source.display()))
+            }
+            Node::Link(path, source) => (path, format!("orphaned link {} -> {}", path.display(), source.display())),
+            _ => continue,
+        };
+        if remove_entry(path, &description, journal, dry_run)? {
+            removed_paths.insert(path.to_path_buf());
+        }
+    }
+
+    if remove_empty_directories {
+        let mut paths = removed_paths.iter()
+            .cloned()
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        for path in paths {
+            remove_empty_parent_directories(&path, targets, &mut removed_paths, journal, dry_run)?;
+        }
+    }
+    Ok(removed_paths.len())
+}
+
+/// Removes the parent directories that are left empty, up to but excluding the target.
+fn remove_empty_parent_directories(
+    path: &Path,
+    targets: &[Target],
+    removed_paths: &mut HashSet<PathBuf>,
+    journal: &mut Journal,
+    dry_run: bool,
+) -> Result<(), LinkerError> {
+    let mut parent = path.parent();
+    while let Some(directory) = parent {
+        let is_within_target = targets.iter()
+            .any(|v| directory.starts_with(&v.path) && directory != Path::new(&v.path));
+        if !is_within_target || removed_paths.contains(directory) || !is_empty(directory, removed_paths) {
+            break;
+        }
+
+        let description = format!("empty directory {}", directory.display());
+        if !remove_entry(directory, &description, journal, dry_run)? {
+            break;
+        }
+        removed_paths.insert(directory.to_path_buf());
+        parent = directory.parent();
+    }
+    Ok(())
+}
+
+/// Whether the directory is empty once the removed paths are gone, which are still around when
+/// running with `--dry-run`.
+fn is_empty(directory: &Path, removed_paths: &HashSet<PathBuf>) -> bool {
+    fs::read_dir(directory)
+        .map(|entries| {
+            entries.filter_map(|v| v.ok())
+                .all(|v| removed_paths.contains(&v.path()))
+        })
+        .unwrap_or(false)
+}
+
+pub(crate) fn remove_entry(
+    path: &Path,
+    description: &str,
+    journal: &mut Journal,
+    dry_run: bool,
+) -> Result<bool, LinkerError> {
+    if dry_run {
+        info!("Removing {}", description);
+        return Ok(true);
+    }
+
+    match journal.remove(path) {
+        Ok(_) => {
+            info!("Removed {}", description);
+            Ok(true)
+        }
+        Err(e) if journal.is_transactional() => Err(e),
+        Err(e) => {
+            warn!("{}", e);
+            Ok(false)
+        }
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::os::unix::fs as unix_fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use crate::collect_nodes::collect_nodes;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn as_string(path: &Path) -> String {
+        path.to_str()
+            .map(|v| v.to_string())
+            .expect("Unable to transform path to string")
+    }
+
+    #[test]
+    fn find_expected_links_with_branch() {
+        let nodes = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/source/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/source/branch/name.pkg.tar.zst")),
+                    Node::Leaf(PathBuf::from("/var/tmp/source/branch/name.sig")),
+                ],
+            ),
+        ];
+        let link_maps = vec![
+            LinkMap::new("(.*)\\.pkg\\.tar\\.zst".to_string(), "/var/tmp/target".to_string()).unwrap(),
+        ];
+        let expected = vec![
+            (
+                Node::Link(
+                    PathBuf::from("/var/tmp/target/name.pkg.tar.zst"),
+                    PathBuf::from("/var/tmp/source/branch/name.pkg.tar.zst"),
+                ),
+                LinkOptions::default(),
+            ),
+        ];
+
+        let actual = find_expected_links(&nodes, &link_maps, MatchStrategy::Last, &LinkOptions::default());
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn find_prunable_links_with_stale_and_orphaned_links() {
+        let target_nodes = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/target/branch"),
+                vec![
+                    Node::BrokenLink(
+                        PathBuf::from("/var/tmp/target/branch/removed"),
+                        PathBuf::from("../../source/removed"),
+                    ),
+                ],
+            ),
+            Node::Leaf(PathBuf::from("/var/tmp/target/leaf")),
+            Node::Link(PathBuf::from("/var/tmp/target/expected"), PathBuf::from("/var/tmp/source/expected")),
+            Node::Link(PathBuf::from("/var/tmp/target/suffixed~1"), PathBuf::from("/var/tmp/source/suffixed")),
+            Node::Link(PathBuf::from("/var/tmp/target/orphaned"), PathBuf::from("/var/tmp/source/orphaned")),
+            Node::Link(PathBuf::from("/var/tmp/target/other"), PathBuf::from("/var/tmp/other/leaf")),
+        ];
+        let expected_links = vec![
+            Node::Link(PathBuf::from("/var/tmp/target/expected"), PathBuf::from("/var/tmp/source/expected")),
+            Node::Link(PathBuf::from("/var/tmp/target/suffixed"), PathBuf::from("/var/tmp/source/suffixed")),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/source".to_string(), 0),
+        ];
+        let expected = vec![
+            Node::BrokenLink(PathBuf::from("/var/tmp/target/branch/removed"), PathBuf::from("../../source/removed")),
+            Node::Link(PathBuf::from("/var/tmp/target/orphaned"), PathBuf::from("/var/tmp/source/orphaned")),
+        ];
+
+        let actual = find_prunable_links(&target_nodes, &expected_links, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn find_prunable_links_with_broken_link_outside_sources() {
+        let target_nodes = vec![
+            Node::BrokenLink(PathBuf::from("/var/tmp/target/relative"), PathBuf::from("../other/removed")),
+            Node::BrokenLink(PathBuf::from("/var/tmp/target/absolute"), PathBuf::from("/var/tmp/other/removed")),
+            Node::BrokenLink(PathBuf::from("/var/tmp/target/source"), PathBuf::from("/var/tmp/source/removed")),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/source".to_string(), 0),
+        ];
+        let expected = vec![
+            Node::BrokenLink(PathBuf::from("/var/tmp/target/source"), PathBuf::from("/var/tmp/source/removed")),
+        ];
+
+        let actual = find_prunable_links(&target_nodes, &[], &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn prune_links_with_empty_directories() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let targets_path = path.join("targets");
+        fs::create_dir_all(targets_path.join("branch").join("nested"))
+            .expect("Unable to create directory");
+        unix_fs::symlink("missing", targets_path.join("branch").join("link"))
+            .expect("Unable to create symbolic link");
+        unix_fs::symlink("missing", targets_path.join("branch").join("nested").join("link"))
+            .expect("Unable to create symbolic link");
+        let links = vec![
+            Node::BrokenLink(targets_path.join("branch").join("link"), PathBuf::from("missing")),
+            Node::BrokenLink(
+                targets_path.join("branch").join("nested").join("link"),
+                PathBuf::from("missing"),
+            ),
+        ];
+        let targets = vec![
+            Target::new(as_string(&targets_path)),
+        ];
+        let expected = Ok(4);
+
+        let actual = prune_links(&links, &targets, true, &mut Journal::disabled(), false);
+
+        assert_eq!(expected, actual);
+        assert_eq!(Vec::<Node>::new(), collect_nodes(&targets_path).nodes);
+    }
+
+    #[test]
+    fn prune_links_without_removing_empty_directories() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let targets_path = path.join("targets");
+        fs::create_dir_all(targets_path.join("branch"))
+            .expect("Unable to create directory");
+        unix_fs::symlink("missing", targets_path.join("branch").join("link"))
+            .expect("Unable to create symbolic link");
+        let links = vec![
+            Node::BrokenLink(targets_path.join("branch").join("link"), PathBuf::from("missing")),
+        ];
+        let targets = vec![
+            Target::new(as_string(&targets_path)),
+        ];
+        let expected = Ok(1);
+
+        let actual = prune_links(&links, &targets, false, &mut Journal::disabled(), false);
+
+        assert_eq!(expected, actual);
+        assert_eq!(
+            vec![Node::Branch(PathBuf::from(as_string(&targets_path.join("branch"))), vec![])],
+            collect_nodes(&targets_path).nodes
+        );
+    }
+
+    #[test]
+    fn prune_links_with_dry_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let targets_path = path.join("targets");
+        fs::create_dir_all(targets_path.join("branch"))
+            .expect("Unable to create directory");
+        File::create(targets_path.join("leaf"))
+            .expect("Unable to create file");
+        unix_fs::symlink("missing", targets_path.join("branch").join("link"))
+            .expect("Unable to create symbolic link");
+        let links = vec![
+            Node::BrokenLink(targets_path.join("branch").join("link"), PathBuf::from("missing")),
+        ];
+        let targets = vec![
+            Target::new(as_string(&targets_path)),
+        ];
+        let expected_nodes = collect_nodes(&targets_path).nodes;
+        let expected = Ok(2);
+
+        let actual = prune_links(&links, &targets, true, &mut Journal::disabled(), true);
+
+        assert_eq!(expected, actual);
+        assert_eq!(expected_nodes, collect_nodes(&targets_path).nodes);
+    }
+}
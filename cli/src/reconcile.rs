@@ -0,0 +1,305 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use crate::configuration::{LinkMode, LinkOptions, Source};
+use crate::filter_copied_nodes::is_linked;
+use crate::filter_target_nodes::{filter_target_nodes, resolve_followed_link_source};
+use crate::journal::Journal;
+use crate::link::{create_link_for_node, is_suffixed_link_path};
+use crate::linker_error::LinkerError;
+use crate::node::Node;
+use crate::prune::{is_within_sources, remove_entry};
+
+/// Change needed for the links within the targets to converge with the link maps.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub(crate) enum Change {
+    /// Link into the sources that none of the link maps would produce.
+    Delete(Node),
+    /// Link that exists at a path that the link maps no longer produce, i.e. the previous path
+    /// together with the link at its desired path.
+    Move(PathBuf, Node, LinkOptions),
+    /// Link that doesn't exist yet.
+    Create(Node, LinkOptions),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Delete(Node::Link(path, source)) => {
+                write!(f, "Delete {} -> {}", path.display(), source.display())
+            }
+            Change::Move(from, Node::Link(path, source), _) => {
+                write!(f, "Move {} to {} -> {}", from.display(), path.display(), source.display())
+            }
+            Change::Create(Node::Link(path, source), _) => {
+                write!(f, "Create {} -> {}", path.display(), source.display())
+            }
+            Change::Delete(node) | Change::Move(_, node, _) | Change::Create(node, _) => write!(f, "{:?}", node),
+        }
+    }
+}
+
+/// Compares the desired links, from the link maps, with the links into the sources that exist
+/// within the targets. An existing link for the same source is moved to the desired path rather
+/// than creating a new link, and existing links that aren't desired are deleted. Deletions come
+/// first so that they can free the path of a created or moved link. A hard link, reflink or copy
+/// of the source at the desired path is treated as an existing link.
+pub(crate) fn plan_changes(
+    desired_links: &[(Node, LinkOptions)],
+    target_nodes: &[Node],
+    sources: &[Source],
+) -> Vec<Change> {
+    let existing_links = filter_target_nodes(target_nodes).into_iter()
+        .filter_map(|v| match v {
+            Node::Link(path, source) if is_within_sources(&source, sources) => Some((path, source)),
+            _ => None,
+        })
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+    let mut remaining_links = existing_links.iter()
+        .filter(|(path, source)| {
+            !desired_links.iter()
+                .any(|(link, _)| is_same_link(link, path, source))
+        })
+        .cloned()
+        .collect::<Vec<(PathBuf, PathBuf)>>();
+
+    let mut changes = Vec::new();
+    for (link, options) in desired_links {
+        let Node::Link(desired_path, desired_source) = link else {
+            continue;
+        };
+        if existing_links.iter().any(|(path, source)| is_same_link(link, path, source))
+            || is_copied_link(desired_path, desired_source, options) {
+            continue;
+        }
+
+        match remaining_links.iter().position(|(_, source)| source == desired_source) {
+            Some(index) => {
+                let (path, _) = remaining_links.remove(index);
+                changes.push(Change::Move(path, link.to_owned(), options.to_owned()));
+            }
+            None => changes.push(Change::Create(link.to_owned(), options.to_owned())),
+        }
+    }
+
+    let deletions = remaining_links.into_iter()
+        .map(|(path, source)| Change::Delete(Node::Link(path, source)));
+    deletions.chain(changes).collect()
+}
+
+fn is_copied_link(path: &Path, source: &Path, options: &LinkOptions) -> bool {
+    options.modes.iter().any(|v| *v != LinkMode::Symlink) && is_linked(path, source, &options.modes)
+}
+
+/// A link created next to the link path by the `suffix` conflict policy, e.g. `name~1`, is the same
+/// link.
+fn is_same_link(link: &Node, path: &Path, source: &Path) -> bool {
+    match link {
+        Node::Link(link_path, link_source) => {
+            (link_path == path || is_suffixed_link_path(path, link_path)) && link_source == source
+        }
+        _ => false,
+    }
+}
+
+/// Applies the changes in order, a link is only removed from its previous path once it has been
+/// created at the desired path. Links to a followed link within the sources are created to its
+/// resolved path. Returns the number of applied changes.
+pub(crate) fn apply_changes(
+    changes: &[Change],
+    followed_links: &[(PathBuf, PathBuf)],
+    journal: &mut Journal,
+) -> Result<usize, LinkerError> {
+    let mut applied_changes = 0;
+    for change in changes {
+        let applied = match change {
+            Change::Delete(link) => remove_link(link, journal)?,
+            Change::Move(from, link, options) => {
+                let link = resolve_followed_link_source(link, followed_links);
+                create_link_for_node(&link, options, journal)?
+                    && remove_entry(from, &format!("previous link {}", from.display()), journal, false)?
+            }
+            Change::Create(link, options) => {
+                create_link_for_node(&resolve_followed_link_source(link, followed_links), options, journal)?
+            }
+        };
+        if applied {
+            applied_changes += 1;
+        }
+    }
+    Ok(applied_changes)
+}
+
+fn remove_link(link: &Node, journal: &mut Journal) -> Result<bool, LinkerError> {
+    match link {
+        Node::Link(path, source) => {
+            remove_entry(path, &format!("link {} -> {}", path.display(), source.display()), journal, false)
+        }
+        _ => Ok(false),
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::fs::File;
+    use std::os::unix::fs as unix_fs;
+    use std::path::PathBuf;
+
+    use tempfile::TempDir;
+
+    use crate::collect_nodes::collect_nodes;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn link<P: AsRef<Path>, S: AsRef<Path>>(path: P, source: S) -> Node {
+        Node::Link(path.as_ref().to_path_buf(), source.as_ref().to_path_buf())
+    }
+
+    #[test]
+    fn plan_changes_with_linked_nodes() {
+        let desired_links = vec![
+            (link("/var/tmp/target/name", "/var/tmp/source/name"), LinkOptions::default()),
+        ];
+        let target_nodes = vec![
+            link("/var/tmp/target/name", "/var/tmp/source/name"),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/source".to_string(), 0),
+        ];
+        let expected: Vec<Change> = Vec::new();
+
+        let actual = plan_changes(&desired_links, &target_nodes, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn plan_changes_with_suffixed_link() {
+        let desired_links = vec![
+            (link("/var/tmp/target/name", "/var/tmp/source/name"), LinkOptions::default()),
+        ];
+        let target_nodes = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/target/name")),
+            link("/var/tmp/target/name~1", "/var/tmp/source/name"),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/source".to_string(), 0),
+        ];
+        let expected: Vec<Change> = Vec::new();
+
+        let actual = plan_changes(&desired_links, &target_nodes, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn plan_changes_with_changed_target() {
+        let desired_links = vec![
+            (link("/var/tmp/target/new/name-1", "/var/tmp/source/name-1"), LinkOptions::default()),
+            (link("/var/tmp/target/new/name-2", "/var/tmp/source/name-2"), LinkOptions::default()),
+        ];
+        let target_nodes = vec![
+            Node::Branch(
+                PathBuf::from("/var/tmp/target/old"),
+                vec![
+                    link("/var/tmp/target/old/name-1", "/var/tmp/source/name-1"),
+                    link("/var/tmp/target/old/name-3", "/var/tmp/source/name-3"),
+                    link("/var/tmp/target/old/other", "/var/tmp/other/name"),
+                ],
+            ),
+        ];
+        let sources = vec![
+            Source::new("/var/tmp/source".to_string(), 0),
+        ];
+        let expected = vec![
+            Change::Delete(link("/var/tmp/target/old/name-3", "/var/tmp/source/name-3")),
+            Change::Move(
+                PathBuf::from("/var/tmp/target/old/name-1"),
+                link("/var/tmp/target/new/name-1", "/var/tmp/source/name-1"),
+                LinkOptions::default(),
+            ),
+            Change::Create(link("/var/tmp/target/new/name-2", "/var/tmp/source/name-2"), LinkOptions::default()),
+        ];
+
+        let actual = plan_changes(&desired_links, &target_nodes, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn plan_changes_with_hard_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = path.join("source").join("name");
+        fs::create_dir_all(path.join("source"))
+            .expect("Unable to create directory");
+        fs::write(&source, "name")
+            .expect("Unable to create file");
+        fs::hard_link(&source, path.join("name"))
+            .expect("Unable to create hard link");
+        let options = LinkOptions { modes: vec![LinkMode::Hardlink, LinkMode::Copy], ..LinkOptions::default() };
+        let desired_links = vec![
+            (link(path.join("name"), &source), options),
+        ];
+        let target_nodes = collect_nodes(&path).nodes;
+        let sources = vec![
+            Source::new(path.join("source").to_string_lossy().to_string(), 0),
+        ];
+        let expected: Vec<Change> = Vec::new();
+
+        let actual = plan_changes(&desired_links, &target_nodes, &sources);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn apply_changes_with_move() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let source = path.join("source");
+        File::create(&source)
+            .expect("Unable to create file");
+        let previous_path = path.join("previous");
+        unix_fs::symlink(&source, &previous_path)
+            .expect("Unable to create symbolic link");
+        let changes = vec![
+            Change::Move(previous_path, link(path.join("desired"), &source), LinkOptions::default()),
+        ];
+        let expected = Ok(1);
+
+        let actual = apply_changes(&changes, &[], &mut Journal::disabled());
+
+        assert_eq!(expected, actual);
+        assert_eq!(
+            vec![
+                Node::Leaf(source.clone()),
+                link(path.join("desired"), &source),
+            ],
+            collect_nodes(&path).nodes
+        );
+    }
+}
@@ -0,0 +1,299 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::configuration::{LinkMap, MatchStrategy};
+use crate::match_link_maps::match_link_maps;
+use crate::node::Node;
+
+/// Source node that would be linked to the same path as a node from a source with higher
+/// precedence, and as such will not be linked.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ShadowedNode {
+    pub path: PathBuf,
+    pub shadowed_by: PathBuf,
+    pub link: PathBuf,
+}
+
+/// Removes the source nodes that are shadowed by a node with the same basename from a source with
+/// higher precedence. The nodes are expected to be ordered by the precedence of their source.
+pub fn shadow_source_nodes(
+    nodes: &[Node],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+) -> (Vec<Node>, Vec<ShadowedNode>) {
+    let mut links: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut shadowed_nodes: Vec<ShadowedNode> = Vec::new();
+    let remaining_nodes = shadow_nodes(nodes, link_maps, match_strategy, &mut links, &mut shadowed_nodes);
+
+    (remaining_nodes, shadowed_nodes)
+}
+
+fn shadow_nodes(
+    nodes: &[Node],
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    links: &mut HashMap<PathBuf, PathBuf>,
+    shadowed_nodes: &mut Vec<ShadowedNode>,
+) -> Vec<Node> {
+    nodes.iter()
+        .filter_map(|n| shadow_node(n, link_maps, match_strategy, links, shadowed_nodes))
+        .collect()
+}
+
+/// Nodes linked by several link maps are shadowed as soon as one of their links is shadowed, since
+/// linking the remaining links would otherwise mix nodes from different sources.
+fn shadow_node(
+    node: &Node,
+    link_maps: &[LinkMap],
+    match_strategy: MatchStrategy,
+    links: &mut HashMap<PathBuf, PathBuf>,
+    shadowed_nodes: &mut Vec<ShadowedNode>,
+) -> Option<Node> {
+    let matched_links: Vec<(PathBuf, PathBuf)> = match_link_maps(node, link_maps, match_strategy)
+        .into_iter()
+        .filter_map(|v| match v {
+            Node::Link(link, path) => Some((link, path)),
+            _ => None,
+        })
+        .collect();
+    if !matched_links.is_empty() {
+        let shadowed: Vec<ShadowedNode> = matched_links.iter()
+            .filter_map(|(link, path)| {
+                links.get(link)
+                    .map(|shadowed_by| ShadowedNode {
+                        path: path.to_owned(),
+                        shadowed_by: shadowed_by.to_owned(),
+                        link: link.to_owned(),
+                    })
+            })
+            .collect();
+        if !shadowed.is_empty() {
+            shadowed_nodes.extend(shadowed);
+            return None;
+        }
+
+        links.extend(matched_links);
+        return Some(node.to_owned());
+    }
+
+    match node {
+        Node::Branch(path, children) if !children.is_empty() => {
+            let remaining_nodes = shadow_nodes(children, link_maps, match_strategy, links, shadowed_nodes);
+            if !remaining_nodes.is_empty() {
+                Some(Node::Branch(path.to_owned(), remaining_nodes))
+            } else {
+                None
+            }
+        }
+        _ => Some(node.to_owned()),
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn shadow_source_nodes_without_nodes() {
+        let nodes: Vec<Node> = Vec::new();
+        let link_maps: Vec<LinkMap> = Vec::new();
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (Vec::new(), Vec::new());
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn shadow_source_nodes_without_matching_link_map() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf")),
+        ];
+        let link_maps: Vec<LinkMap> = Vec::new();
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (
+            vec![
+                Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+                Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf")),
+            ],
+            Vec::new(),
+        );
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn shadow_source_nodes_with_duplicate_leaf() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf")),
+            Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf-2")),
+        ];
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap()
+        ];
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (
+            vec![
+                Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+                Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf-2")),
+            ],
+            vec![
+                ShadowedNode {
+                    path: PathBuf::from("/var/tmp/source-2/leaf"),
+                    shadowed_by: PathBuf::from("/var/tmp/source-1/leaf"),
+                    link: PathBuf::from("/var/tmp/targets/leaf"),
+                },
+            ],
+        );
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn shadow_source_nodes_with_duplicate_leaf_in_branch() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            Node::Branch(
+                PathBuf::from("/var/tmp/source-2/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/source-2/branch/leaf")),
+                ],
+            ),
+            Node::Branch(
+                PathBuf::from("/var/tmp/source-2/empty"),
+                Vec::new(),
+            ),
+        ];
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap()
+        ];
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (
+            vec![
+                Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+                Node::Branch(
+                    PathBuf::from("/var/tmp/source-2/empty"),
+                    Vec::new(),
+                ),
+            ],
+            vec![
+                ShadowedNode {
+                    path: PathBuf::from("/var/tmp/source-2/branch/leaf"),
+                    shadowed_by: PathBuf::from("/var/tmp/source-1/leaf"),
+                    link: PathBuf::from("/var/tmp/targets/leaf"),
+                },
+            ],
+        );
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn shadow_source_nodes_with_duplicate_linked_to_different_targets() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            Node::Branch(
+                PathBuf::from("/var/tmp/source-2/branch"),
+                vec![
+                    Node::Leaf(PathBuf::from("/var/tmp/source-2/branch/leaf")),
+                ],
+            ),
+        ];
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap(),
+            LinkMap::new(
+                "branch".to_string(),
+                "/var/tmp/targets".to_string(),
+            ).unwrap(),
+        ];
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (
+            vec![
+                Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+                Node::Branch(
+                    PathBuf::from("/var/tmp/source-2/branch"),
+                    vec![
+                        Node::Leaf(PathBuf::from("/var/tmp/source-2/branch/leaf")),
+                    ],
+                ),
+            ],
+            Vec::new(),
+        );
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::Last);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn shadow_source_nodes_with_duplicate_leaf_using_all() {
+        let nodes: Vec<Node> = vec![
+            Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            Node::Leaf(PathBuf::from("/var/tmp/source-2/leaf")),
+        ];
+        let link_maps: Vec<LinkMap> = vec![
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets-1".to_string(),
+            ).unwrap(),
+            LinkMap::new(
+                "leaf".to_string(),
+                "/var/tmp/targets-2".to_string(),
+            ).unwrap(),
+        ];
+        let expected: (Vec<Node>, Vec<ShadowedNode>) = (
+            vec![
+                Node::Leaf(PathBuf::from("/var/tmp/source-1/leaf")),
+            ],
+            vec![
+                ShadowedNode {
+                    path: PathBuf::from("/var/tmp/source-2/leaf"),
+                    shadowed_by: PathBuf::from("/var/tmp/source-1/leaf"),
+                    link: PathBuf::from("/var/tmp/targets-1/leaf"),
+                },
+                ShadowedNode {
+                    path: PathBuf::from("/var/tmp/source-2/leaf"),
+                    shadowed_by: PathBuf::from("/var/tmp/source-1/leaf"),
+                    link: PathBuf::from("/var/tmp/targets-2/leaf"),
+                },
+            ],
+        );
+
+        let actual = shadow_source_nodes(&nodes, &link_maps, MatchStrategy::All);
+
+        assert_eq!(expected, actual)
+    }
+}
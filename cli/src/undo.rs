@@ -0,0 +1,345 @@
+/*
+ * linker
+ * Copyright (C) 2021 raatiniemi
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, version 2 of the License.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use std::io;
+use std::path::Path;
+use std::process;
+
+use log::{info, warn};
+
+use crate::journal::{
+    EntryState, JournalRecord, Operation, RunStatus, find_run_ids, journal_path, read_journal, write_status,
+};
+use crate::link::is_process_running;
+use crate::linker_error::LinkerError;
+
+/// Reverts the changes of the run, or the latest run that hasn't been reverted, in reverse order.
+/// Entries that have changed since the run are left as they are, and the run is then marked as
+/// partially reverted so that it can be undone again. Returns the number of changes that were
+/// reverted.
+pub(crate) fn undo_run(directory: &Path, run_id: Option<&str>, dry_run: bool) -> Result<usize, LinkerError> {
+    let run_id = match run_id {
+        Some(run_id) if !is_valid_run_id(run_id) => return Err(LinkerError::InvalidRunId(run_id.to_string())),
+        Some(run_id) => run_id.to_string(),
+        None => find_latest_run_id(directory)?,
+    };
+    let path = journal_path(directory, &run_id);
+    let record = match read_journal(&path) {
+        Ok(record) => record,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(LinkerError::UnknownRun(run_id)),
+        Err(e) => return Err(LinkerError::UnableToReadJournal(path, e)),
+    };
+    if is_reverted(&record) {
+        return Err(LinkerError::RunAlreadyReverted(run_id));
+    }
+
+    info!("Undoing {} changes from run {}", record.changes.len(), run_id);
+    let reverted_changes = record.changes.iter()
+        .rev()
+        .filter(|(operation, state)| undo_change(operation, state.as_ref(), dry_run))
+        .count();
+    let skipped_changes = record.changes.len() - reverted_changes;
+    if skipped_changes > 0 {
+        warn!("{} changes from run {} were not undone", skipped_changes, run_id);
+    }
+
+    if !dry_run {
+        write_status(&path, reverted_status(RunStatus::Undone, skipped_changes))
+            .map_err(LinkerError::UnableToWriteJournal)?;
+    }
+    Ok(reverted_changes)
+}
+
+/// The run id is used as the file stem of the journal, i.e. it can't refer to any other directory.
+fn is_valid_run_id(run_id: &str) -> bool {
+    !run_id.is_empty() && run_id != "." && run_id != ".." && !run_id.contains(['/', '\0'])
+}
+
+fn reverted_status(status: RunStatus, skipped_changes: usize) -> RunStatus {
+    if skipped_changes > 0 {
+        RunStatus::PartiallyReverted
+    } else {
+        status
+    }
+}
+
+/// Rolls back the transactional runs that were interrupted, i.e. runs without a status in their
+/// journal whose process is no longer running. Returns the number of runs that were rolled back.
+pub(crate) fn recover_interrupted_runs(directory: &Path, dry_run: bool) -> Result<usize, LinkerError> {
+    let run_ids = match find_run_ids(directory) {
+        Ok(run_ids) => run_ids,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(LinkerError::UnableToReadJournal(directory.to_path_buf(), e)),
+    };
+
+    let mut recovered_runs = 0;
+    for run_id in run_ids.iter().rev() {
+        let path = journal_path(directory, run_id);
+        let record = read_journal(&path)
+            .map_err(|e| LinkerError::UnableToReadJournal(path.to_path_buf(), e))?;
+        if !record.transactional || record.status.is_some() || is_running(run_id) {
+            continue;
+        }
+
+        warn!("Rolling back {} changes from interrupted run {}", record.changes.len(), run_id);
+        let reverted_changes = record.changes.iter()
+            .rev()
+            .filter(|(operation, state)| undo_change(operation, state.as_ref(), dry_run))
+            .count();
+        let skipped_changes = record.changes.len() - reverted_changes;
+        if skipped_changes > 0 {
+            warn!("{} changes from run {} were not rolled back", skipped_changes, run_id);
+        }
+
+        if !dry_run {
+            write_status(&path, reverted_status(RunStatus::RolledBack, skipped_changes))
+                .map_err(LinkerError::UnableToWriteJournal)?;
+        }
+        recovered_runs += 1;
+    }
+    Ok(recovered_runs)
+}
+
+/// The run id ends with the id of the process of the run.
+fn is_running(run_id: &str) -> bool {
+    let process_id = match run_id.rsplit_once('-').and_then(|(_, v)| v.parse::<i32>().ok()) {
+        Some(process_id) => process_id,
+        None => return false,
+    };
+    process_id != process::id() as i32 && is_process_running(process_id)
+}
+
+fn find_latest_run_id(directory: &Path) -> Result<String, LinkerError> {
+    let run_ids = match find_run_ids(directory) {
+        Ok(run_ids) => run_ids,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(LinkerError::NoRunToUndo),
+        Err(e) => return Err(LinkerError::UnableToReadJournal(directory.to_path_buf(), e)),
+    };
+
+    run_ids.into_iter()
+        .rev()
+        .find(|v| {
+            read_journal(&journal_path(directory, v))
+                .is_ok_and(|v| !v.changes.is_empty() && !is_reverted(&v))
+        })
+        .ok_or(LinkerError::NoRunToUndo)
+}
+
+fn is_reverted(record: &JournalRecord) -> bool {
+    matches!(record.status, Some(RunStatus::Undone) | Some(RunStatus::RolledBack))
+}
+
+fn undo_change(operation: &Operation, state: Option<&EntryState>, dry_run: bool) -> bool {
+    if let Some(path) = find_changed_path(operation, state) {
+        warn!("{:?} has changed since the run, unable to undo {}", path, operation);
+        return false;
+    }
+    if dry_run {
+        info!("Undoing {}", operation);
+        return true;
+    }
+
+    match operation.revert() {
+        Ok(_) => {
+            info!("Undid {}", operation);
+            true
+        }
+        Err(e) => {
+            warn!("Unable to undo {}: {}", operation, e);
+            false
+        }
+    }
+}
+
+/// Finds the path that has changed since the run, i.e. the resulting entry has been replaced or
+/// modified, or something has been created where an entry was removed or renamed from.
+fn find_changed_path<'a>(operation: &'a Operation, state: Option<&EntryState>) -> Option<&'a Path> {
+    if let Some(path) = operation.path() {
+        if !state.is_some_and(|v| v.is_unchanged(path)) {
+            return Some(path);
+        }
+    }
+
+    match operation {
+        Operation::Remove(path, _) if path.symlink_metadata().is_ok() => Some(path),
+        Operation::Rename(from, _) if from.symlink_metadata().is_ok() => Some(from),
+        _ => None,
+    }
+}
+
+//noinspection DuplicatedCode
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs as unix_fs;
+    use std::path::{Path, PathBuf};
+
+    use tempfile::TempDir;
+
+    use crate::journal::Journal;
+
+    use super::*;
+
+    fn create_temporary_directory() -> TempDir {
+        TempDir::new()
+            .expect("Unable to create temporary directory")
+    }
+
+    fn create_file_with_content(path: &Path, content: &str) {
+        fs::write(path, content)
+            .unwrap_or_else(|_| panic!("Unable to create file at: {:?}", path));
+    }
+
+    fn create_link(journal: &mut Journal, source: &Path, path: &Path) {
+        journal.create_link(path, || {
+            unix_fs::symlink(source, path)
+                .map_err(LinkerError::UnableToCreateSymlink)
+        }).expect("Unable to create link");
+    }
+
+    #[test]
+    fn undo_run_with_latest_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_path = path.join("journal");
+        create_file_with_content(&path.join("source"), "source");
+        create_file_with_content(&path.join("replaced"), "replaced");
+        let mut journal = Journal::create(&journal_path, false)
+            .expect("Unable to create journal");
+        journal.create_directory(&path.join("branch")).unwrap();
+        create_link(&mut journal, &path.join("source"), &path.join("branch").join("link"));
+        unix_fs::symlink(path.join("source"), path.join("replacement")).unwrap();
+        journal.replace(&path.join("replaced"), &path.join("replacement")).unwrap();
+        journal.commit().unwrap();
+        let expected = Ok(3);
+
+        let actual = undo_run(&journal_path, None, false);
+
+        assert_eq!(expected, actual);
+        assert!(path.join("branch").symlink_metadata().is_err());
+        assert_eq!("replaced", fs::read_to_string(path.join("replaced")).unwrap());
+        assert_eq!(Err(LinkerError::NoRunToUndo), undo_run(&journal_path, None, false))
+    }
+
+    #[test]
+    fn undo_run_with_changed_link() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_path = path.join("journal");
+        create_file_with_content(&path.join("source"), "source");
+        let mut journal = Journal::create(&journal_path, false)
+            .expect("Unable to create journal");
+        create_link(&mut journal, &path.join("source"), &path.join("link-1"));
+        create_link(&mut journal, &path.join("source"), &path.join("link-2"));
+        journal.commit().unwrap();
+        fs::remove_file(path.join("link-2")).unwrap();
+        create_file_with_content(&path.join("link-2"), "changed");
+        let expected = Ok(1);
+
+        let actual = undo_run(&journal_path, None, false);
+
+        assert_eq!(expected, actual);
+        assert!(path.join("link-1").symlink_metadata().is_err());
+        assert_eq!("changed", fs::read_to_string(path.join("link-2")).unwrap());
+        let run_id = find_run_ids(&journal_path).unwrap().remove(0);
+        let record = read_journal(&journal_path.join(format!("{}.journal", run_id))).unwrap();
+        assert_eq!(Some(RunStatus::PartiallyReverted), record.status)
+    }
+
+    #[test]
+    fn undo_run_with_dry_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_path = path.join("journal");
+        create_file_with_content(&path.join("source"), "source");
+        let mut journal = Journal::create(&journal_path, false)
+            .expect("Unable to create journal");
+        create_link(&mut journal, &path.join("source"), &path.join("link"));
+        journal.commit().unwrap();
+        let expected = Ok(1);
+
+        let actual = undo_run(&journal_path, None, true);
+
+        assert_eq!(expected, actual);
+        assert!(path.join("link").symlink_metadata().is_ok());
+        assert_eq!(Ok(1), undo_run(&journal_path, None, false))
+    }
+
+    #[test]
+    fn undo_run_with_unknown_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let expected = Err(LinkerError::UnknownRun("1700000000-1".to_string()));
+
+        let actual = undo_run(&path, Some("1700000000-1"), false);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn undo_run_with_invalid_run_id() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let expected = Err(LinkerError::InvalidRunId("../1700000000-1".to_string()));
+
+        let actual = undo_run(&path.join("journal"), Some("../1700000000-1"), false);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn undo_run_with_rolled_back_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_path = path.join("journal");
+        let mut journal = Journal::create(&journal_path, true)
+            .expect("Unable to create journal");
+        journal.create_directory(&path.join("branch")).unwrap();
+        journal.rollback();
+        let run_id = find_run_ids(&journal_path).unwrap().remove(0);
+        let expected = Err(LinkerError::RunAlreadyReverted(run_id.to_string()));
+
+        let actual = undo_run(&journal_path, Some(&run_id), false);
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn recover_interrupted_runs_with_interrupted_run() {
+        let directory = create_temporary_directory();
+        let path = PathBuf::from(directory.path());
+        let journal_path = path.join("journal");
+        create_file_with_content(&path.join("source"), "source");
+        let mut journal = Journal::create(&journal_path, true)
+            .expect("Unable to create journal");
+        journal.create_directory(&path.join("branch")).unwrap();
+        create_link(&mut journal, &path.join("source"), &path.join("branch").join("link"));
+        unix_fs::symlink(path.join("source"), path.join("link")).unwrap();
+        create_file_with_content(
+            &journal_path.join("1700000000-1.journal"),
+            &format!("{{\"operation\":\"createLink\",\"path\":{:?}}}\n{{\"completed\":0}}\n", path.join("link")),
+        );
+        let expected = Ok(1);
+
+        let actual = recover_interrupted_runs(&journal_path, false);
+
+        assert_eq!(expected, actual);
+        assert!(path.join("branch").symlink_metadata().is_err());
+        assert!(path.join("link").symlink_metadata().is_ok());
+        assert_eq!(Ok(0), recover_interrupted_runs(&journal_path, false))
+    }
+}